@@ -2,21 +2,38 @@ use std::io::BufWriter;
 use std::ops::Try;
 use std::path::{Path, PathBuf};
 
-use clog::fmt::MarkdownWriter;
-use clog::Clog;
+mod conventional;
+mod keep_a_changelog;
+mod notes;
+mod references;
+mod reverts;
+mod rules;
+
+use clog::fmt::{FormatWriter, MarkdownWriter};
+use clog::{Clog, SectionMap};
+use failure::Fail;
 use git2::{Commit, Repository};
 use serde::{Deserialize, Serialize};
 
-use crate::plugin_support::flow::{Availability, FlowError, ProvisionCapability, Value};
+use crate::builtin_plugins::git::format_date;
+use crate::config::Map;
+use crate::plugin_support::flow::{Availability, FlowError, MergeStrategy, ProvisionCapability, Value};
 use crate::plugin_support::keys::{
-    CURRENT_VERSION, DRY_RUN, FILES_TO_COMMIT, NEXT_VERSION, PROJECT_ROOT, RELEASE_NOTES,
+    CURRENT_VERSION, DRY_RUN, FILES_TO_COMMIT, GIT_REMOTE_URL, NEXT_VERSION, PROJECT_ROOT, REFERENCED_ISSUES,
+    RELEASE_CHANNEL, RELEASE_NOTES, RELEASE_NOTES_DATA, RELEASE_RANGE, RELEASE_TAG,
 };
 use crate::plugin_support::proto::{
     response::{self, PluginResponse},
-    Version,
+    Version, VersionRange,
 };
 use crate::plugin_support::{PluginInterface, PluginStep};
 
+use self::conventional::ConventionalCommit;
+use self::keep_a_changelog::{Category, Links};
+use self::notes::{Author, BreakingChange, CommitEntry, NotesContext, Section};
+use self::references::{IssueLinker, ReferencedIssue};
+use self::rules::{ReleaseRule, RuleSet};
+
 pub struct ClogPlugin {
     config: Config,
     state: State,
@@ -36,6 +53,8 @@ impl ClogPlugin {
 #[derive(Default)]
 struct State {
     release_notes: Option<String>,
+    release_notes_data: Option<NotesContext>,
+    referenced_issues: Option<Vec<ReferencedIssue>>,
     next_version: Option<semver::Version>,
 }
 
@@ -71,17 +90,39 @@ struct DryRunGuard {
 #[derive(Serialize, Deserialize)]
 struct Config {
     changelog: Value<String>,
+    changelog_format: Value<ChangelogFormat>,
+    changelog_categories: Value<Map<String, Category>>,
     ignore: Value<Vec<String>>,
     project_root: Value<String>,
     dry_run: Value<bool>,
     current_version: Value<Version>,
     next_version: Value<semver::Version>,
+    release_channel: Value<Option<String>>,
+    release_range: Value<Option<VersionRange>>,
+    release_rules: Value<Vec<ReleaseRule>>,
+    notes_template: Value<Option<String>>,
+    issue_url: Value<Option<String>>,
+    remote_url: Value<String>,
+    release_tag: Value<String>,
+}
+
+/// Format of the changelog file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum ChangelogFormat {
+    /// Release notes prepended to the file
+    #[default]
+    Clog,
+    /// Release section inserted under `## [Unreleased]`, see [keepachangelog.com](https://keepachangelog.com)
+    KeepAChangelog,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             changelog: Value::with_value("changelog", "Changelog.md".into()),
+            changelog_format: Value::with_default_value("changelog_format"),
+            changelog_categories: Value::with_default_value("changelog_categories"),
             ignore: Value::with_default_value("ignore"),
             project_root: Value::protected(PROJECT_ROOT),
             dry_run: Value::protected(DRY_RUN),
@@ -89,11 +130,165 @@ impl Default for Config {
             next_version: Value::builder(NEXT_VERSION)
                 .required_at(PluginStep::GenerateNotes)
                 .protected()
+                .merge(MergeStrategy::MaxSemver)
+                .build(),
+            release_channel: Value::builder(RELEASE_CHANNEL)
+                .required_at(PluginStep::DeriveNextVersion)
+                .protected()
+                .build(),
+            release_range: Value::builder(RELEASE_RANGE)
+                .required_at(PluginStep::DeriveNextVersion)
+                .protected()
+                .build(),
+            release_rules: Value::with_default_value("release_rules"),
+            notes_template: Value::with_default_value("notes_template"),
+            issue_url: Value::with_default_value("issue_url"),
+            remote_url: Value::builder(GIT_REMOTE_URL)
+                .required_at(PluginStep::GenerateNotes)
+                .protected()
+                .optional()
+                .build(),
+            release_tag: Value::builder(RELEASE_TAG)
+                .required_at(PluginStep::Prepare)
+                .protected()
+                .optional()
                 .build(),
         }
     }
 }
 
+impl Config {
+    /// Source of `notes_template`, the path is relative to the project root
+    fn notes_template(&self) -> Result<Option<String>, Error> {
+        let path = match self.notes_template.as_value() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        std::fs::read_to_string(Path::new(self.project_root.as_value()).join(path))
+            .map(Some)
+            .map_err(|err| Error::NotesTemplateUnreadable(path.clone(), err))
+    }
+
+    /// URL of the git remote, not available without the git plugin
+    fn remote_url(&self) -> Option<&str> {
+        if self.remote_url.is_ready() {
+            Some(self.remote_url.as_value())
+        } else {
+            None
+        }
+    }
+
+    /// Tag of the release as named by the git plugin, or `v<version>` without it
+    fn release_tag(&self) -> String {
+        if self.release_tag.is_ready() {
+            self.release_tag.as_value().clone()
+        } else {
+            format!("v{}", self.next_version.as_value())
+        }
+    }
+
+    fn issue_linker(&self) -> IssueLinker {
+        IssueLinker::new(
+            self.remote_url().unwrap_or_default(),
+            self.issue_url.as_value().as_deref(),
+        )
+    }
+
+    /// Data of the release notes, or of the changelog section of the release if `changelog` is set
+    fn notes_context(&self, changelog: bool) -> Result<NotesContext, failure::Error> {
+        let rules = RuleSet::new(self.release_rules.as_value())?;
+
+        notes_context(
+            self.project_root.as_value(),
+            self.current_version.as_value(),
+            self.next_version.as_value(),
+            &rules,
+            self.remote_url(),
+            &self.issue_linker(),
+            changelog,
+        )
+    }
+
+    /// Issues referenced by the commits of the release
+    fn referenced_issues(&self) -> Result<Vec<ReferencedIssue>, failure::Error> {
+        let rules = RuleSet::new(self.release_rules.as_value())?;
+        let linker = self.issue_linker();
+        let (repo, scope) = open_repository(self.project_root.as_value())?;
+
+        let references: Vec<_> =
+            conventional_commits_since_rev(&repo, &scope, &self.current_version.as_value().rev, &rules)?
+                .into_iter()
+                .map(|(commit, conventional, _)| (commit.id().to_string(), linker.references(&conventional)))
+                .collect();
+
+        Ok(references::referenced_issues(references.iter().flat_map(
+            |(hash, references)| references.iter().map(move |reference| (hash.as_str(), reference)),
+        )))
+    }
+
+    /// Section of the release in a Keep a Changelog file
+    fn keep_a_changelog_release(&self) -> Result<keep_a_changelog::Release, failure::Error> {
+        let current_version = self.current_version.as_value();
+        let next_version = self.next_version.as_value();
+        let categories = self.changelog_categories.as_value();
+        let rules = RuleSet::new(self.release_rules.as_value())?;
+        let linker = self.issue_linker();
+        let (repo, scope) = open_repository(self.project_root.as_value())?;
+
+        let entries = conventional_commits_since_rev(&repo, &scope, &current_version.rev, &rules)?
+            .into_iter()
+            .filter_map(|(_, commit, _)| {
+                let category = categories
+                    .get(&commit.commit_type.to_ascii_lowercase())
+                    .copied()
+                    .or_else(|| Category::of_type(&commit.commit_type))
+                    .or_else(|| commit.is_breaking().then_some(Category::Changed))?;
+                Some((category, keep_a_changelog_entry(&commit, &linker)))
+            })
+            .collect();
+
+        let tag = self.release_tag();
+        let links = self
+            .remote_url()
+            .and_then(notes::repository_url)
+            .map(|repository| Links {
+                unreleased: format!("{}/compare/{}...HEAD", repository, tag),
+                release: match current_version.semver {
+                    Some(_) => format!("{}/compare/{}...{}", repository, current_version.rev, tag),
+                    None => format!("{}/releases/tag/{}", repository, tag),
+                },
+            });
+
+        Ok(keep_a_changelog::Release {
+            version: next_version.to_string(),
+            date: today()?,
+            entries,
+            links,
+        })
+    }
+
+    /// Markdown of the release notes or the changelog section, in the format of `notes_template` if set
+    fn render_notes(&self, context: &NotesContext) -> Result<String, failure::Error> {
+        let project_root = self.project_root.as_value();
+        let current_version = self.current_version.as_value();
+        let next_version = self.next_version.as_value();
+        let rules = RuleSet::new(self.release_rules.as_value())?;
+        let linker = self.issue_linker();
+
+        let template = match self.notes_template()? {
+            Some(template) => template,
+            None if context.changelog => {
+                return render_changelog(project_root, &current_version.rev, next_version, &rules, &linker)
+            }
+            None => return generate_changelog(project_root, &current_version.rev, next_version, &rules, &linker),
+        };
+
+        let path = self.notes_template.as_value().as_deref().unwrap_or_default();
+        Ok(notes::render(path, &template, context)?)
+    }
+}
+
 impl PluginInterface for ClogPlugin {
     fn name(&self) -> response::Name {
         PluginResponse::from_ok("clog".into())
@@ -104,6 +299,12 @@ impl PluginInterface for ClogPlugin {
             ProvisionCapability::builder(RELEASE_NOTES)
                 .after_step(PluginStep::GenerateNotes)
                 .build(),
+            ProvisionCapability::builder(RELEASE_NOTES_DATA)
+                .after_step(PluginStep::GenerateNotes)
+                .build(),
+            ProvisionCapability::builder(REFERENCED_ISSUES)
+                .after_step(PluginStep::GenerateNotes)
+                .build(),
             ProvisionCapability::builder(NEXT_VERSION)
                 .after_step(PluginStep::DeriveNextVersion)
                 .build(),
@@ -122,6 +323,20 @@ impl PluginInterface for ClogPlugin {
 
                 PluginResponse::from_ok(serde_json::to_value(notes)?)
             }
+            "release_notes_data" => {
+                let data = self.state.release_notes_data.as_ref().ok_or_else(|| {
+                    FlowError::DataNotAvailableYet(key.to_owned(), Availability::AfterStep(PluginStep::GenerateNotes))
+                })?;
+
+                PluginResponse::from_ok(serde_json::to_value(data)?)
+            }
+            "referenced_issues" => {
+                let issues = self.state.referenced_issues.as_ref().ok_or_else(|| {
+                    FlowError::DataNotAvailableYet(key.to_owned(), Availability::AfterStep(PluginStep::GenerateNotes))
+                })?;
+
+                PluginResponse::from_ok(serde_json::to_value(issues)?)
+            }
             "next_version" => {
                 let next_version = self.state.next_version.as_ref().ok_or_else(|| {
                     FlowError::DataNotAvailableYet(
@@ -161,6 +376,11 @@ impl PluginInterface for ClogPlugin {
     }
 
     fn pre_flight(&mut self) -> response::Null {
+        RuleSet::new(self.config.release_rules.as_value())?;
+        if let Some(template) = self.config.notes_template()? {
+            let path = self.config.notes_template.as_value().as_deref().unwrap_or_default();
+            notes::validate_template(path, &template)?;
+        }
         PluginResponse::from_ok(())
     }
 
@@ -169,51 +389,33 @@ impl PluginInterface for ClogPlugin {
         let project_root = cfg.project_root.as_value();
         let current_version = cfg.current_version.as_value();
         let ignore = cfg.ignore.as_value();
+        let channel = cfg.release_channel.as_value().as_deref();
+        let rules = RuleSet::new(cfg.release_rules.as_value())?;
 
         let bump = match &current_version.semver {
             None => CommitType::Major,
-            Some(_) => version_bump_since_rev(&project_root, &current_version.rev, &ignore)?,
+            Some(_) => version_bump_since_rev(project_root, &current_version.rev, ignore, &rules)?,
         };
 
-        let next_version = match current_version.semver.clone() {
-            None => semver::Version::new(0, 1, 0),
-            Some(mut version) => {
-                // NB: According to the Semver spec, major version zero is for
-                // the initial development phase is treated slightly differently.
-                // The minor version is incremented for breaking changes
-                // and major is kept at zero until the public API has become more stable.
-                if version.major == 0 {
-                    match bump {
-                        CommitType::Unknown => (),
-                        CommitType::Patch => version.increment_patch(),
-                        CommitType::Minor => version.increment_patch(),
-                        CommitType::Major => version.increment_minor(),
-                    }
-                } else {
-                    match bump {
-                        CommitType::Unknown => (),
-                        CommitType::Patch => version.increment_patch(),
-                        CommitType::Minor => version.increment_minor(),
-                        CommitType::Major => version.increment_major(),
-                    }
-                }
+        let next_version = next_version(current_version.semver.as_ref(), bump, channel);
 
-                version
-            }
-        };
+        if let Some(range) = cfg.release_range.as_value() {
+            check_range(current_version.semver.as_ref(), bump, &next_version, range)?;
+        }
 
-        self.state.next_version.replace(next_version.clone());
+        self.state.next_version.replace(next_version);
 
         PluginResponse::from_ok(())
     }
 
     fn generate_notes(&mut self) -> response::Null {
+        let data = self.config.notes_context(false)?;
+        let referenced_issues = self.config.referenced_issues()?;
         let changelog = {
-            let project_root = self.config.project_root.as_value();
             let current_version = self.config.current_version.as_value();
             let next_version = self.config.next_version.as_value();
 
-            let changelog = generate_changelog(project_root, &current_version.rev, next_version)?;
+            let changelog = self.config.render_notes(&data)?;
 
             log::info!("Changelog for {}..{}", current_version.rev, next_version);
             log::info!("---------------------------------------------------");
@@ -225,66 +427,240 @@ impl PluginInterface for ClogPlugin {
 
         // Store this request as state
         self.state.release_notes.replace(changelog.clone());
+        self.state.release_notes_data.replace(data);
+        self.state.referenced_issues.replace(referenced_issues);
 
         PluginResponse::from_ok(())
     }
 
     fn prepare(&mut self) -> response::Null {
         let cfg = &self.config;
-        let changelog_path = cfg.changelog.as_value();
-        let repo_path = cfg.project_root.as_value();
+        let project_root = cfg.project_root.as_value();
+        let changelog_path = Path::new(project_root).join(cfg.changelog.as_value());
         let is_dry_run = *cfg.dry_run.as_value();
-        let current_version = cfg.current_version.as_value();
-        let next_version = cfg.next_version.as_value();
 
         // Safely store the original changelog for restoration after dry-run is finished
         if is_dry_run {
             log::info!("clog(dry-run): saving original state of changelog file");
             let original_changelog = std::fs::read(&changelog_path).ok();
             self.dry_run_guard.replace(DryRunGuard {
-                changelog_path: Path::new(changelog_path).to_owned(),
+                changelog_path: changelog_path.clone(),
                 original_changelog,
             });
         }
 
-        let mut clog = Clog::with_dir(repo_path)?;
-        clog.changelog(changelog_path)
-            .from(&current_version.rev)
-            .version(format!("v{}", next_version));
-
         log::info!("Writing updated changelog");
-        clog.write_changelog()?;
+        let original_changelog = std::fs::read_to_string(&changelog_path).unwrap_or_default();
+        let changelog = match cfg.changelog_format.as_value() {
+            ChangelogFormat::Clog => {
+                let changelog = cfg.render_notes(&cfg.notes_context(true)?)?;
+                format!("{}\n\n\n{}", changelog, original_changelog)
+            }
+            ChangelogFormat::KeepAChangelog => {
+                keep_a_changelog::insert_release(&original_changelog, &cfg.keep_a_changelog_release()?)
+            }
+        };
+        std::fs::write(&changelog_path, changelog)?;
 
         PluginResponse::from_ok(())
     }
 }
 
-fn version_bump_since_rev(path: &str, rev: &str, ignore: &[String]) -> Result<CommitType, failure::Error> {
-    let repo = Repository::open(path)?;
-    let range = format!("{}..HEAD", rev);
-    log::debug!("analyzing commits {} to determine version bump", range);
+/// Version following `current` after changes of the `bump` type,
+/// pre-releases on a channel are numbered as `<version>-<channel>.<N>`
+fn next_version(current: Option<&semver::Version>, bump: CommitType, channel: Option<&str>) -> semver::Version {
+    let current = match current {
+        Some(current) => current,
+        None => {
+            let mut first = semver::Version::new(0, 1, 0);
+            if let Some(channel) = channel {
+                first.pre = prerelease(channel, 1);
+            }
+            return first;
+        }
+    };
+
+    let channel = match channel {
+        _ if bump == CommitType::Unknown => return current.clone(),
+        Some(channel) => channel,
+        None => return bump_version(current, bump),
+    };
+
+    match prerelease_number(current, channel) {
+        // Changes are already a part of the upcoming release, e.g. a fix after 1.3.0-rc.1 makes 1.3.0-rc.2
+        Some(n) if is_bump_released(current, bump) => {
+            let mut next = current.clone();
+            next.pre = prerelease(channel, n + 1);
+            next
+        }
+        _ => {
+            let mut release = current.clone();
+            release.pre.clear();
+            let mut next = bump_version(&release, bump);
+            next.pre = prerelease(channel, 1);
+            next
+        }
+    }
+}
+
+/// Maintenance branches can't release versions outside of their range
+fn check_range(
+    current: Option<&semver::Version>,
+    bump: CommitType,
+    next: &semver::Version,
+    range: &VersionRange,
+) -> Result<(), Error> {
+    if range.contains(next) {
+        return Ok(());
+    }
+
+    let increment = match current.map(|current| increment_kind(current, bump)) {
+        None => "initial",
+        Some(CommitType::Major) => "major",
+        Some(CommitType::Minor) => "minor",
+        Some(CommitType::Patch) | Some(CommitType::Unknown) => "patch",
+    };
+
+    Err(Error::VersionOutOfRange(next.clone(), *range, increment))
+}
+
+fn prerelease(channel: &str, n: u64) -> Vec<semver::Identifier> {
+    vec![
+        semver::Identifier::AlphaNumeric(channel.to_owned()),
+        semver::Identifier::Numeric(n),
+    ]
+}
+
+/// Number of the pre-release on the channel, `None` if the version is not a pre-release on this channel
+fn prerelease_number(version: &semver::Version, channel: &str) -> Option<u64> {
+    match version.pre.as_slice() {
+        [semver::Identifier::AlphaNumeric(id), rest @ ..] if id == channel => match rest {
+            [semver::Identifier::Numeric(n)] => Some(*n),
+            _ => Some(0),
+        },
+        _ => None,
+    }
+}
+
+/// Whether the release the pre-release is made for already includes the increment of this kind,
+/// e.g. 1.3.0 is a minor release, so features don't need another minor increment
+fn is_bump_released(version: &semver::Version, bump: CommitType) -> bool {
+    match increment_kind(version, bump) {
+        CommitType::Unknown | CommitType::Patch => true,
+        CommitType::Minor => version.patch == 0,
+        CommitType::Major => version.minor == 0 && version.patch == 0,
+    }
+}
+
+/// Part of the version to increment for changes of the `bump` type
+fn increment_kind(version: &semver::Version, bump: CommitType) -> CommitType {
+    // NB: According to the Semver spec, major version zero is for
+    // the initial development phase is treated slightly differently.
+    // The minor version is incremented for breaking changes
+    // and major is kept at zero until the public API has become more stable.
+    if version.major == 0 {
+        match bump {
+            CommitType::Major => CommitType::Minor,
+            CommitType::Minor => CommitType::Patch,
+            other => other,
+        }
+    } else {
+        bump
+    }
+}
+
+fn bump_version(version: &semver::Version, bump: CommitType) -> semver::Version {
+    let mut next = version.clone();
+    match increment_kind(version, bump) {
+        CommitType::Unknown => (),
+        CommitType::Patch => next.increment_patch(),
+        CommitType::Minor => next.increment_minor(),
+        CommitType::Major => next.increment_major(),
+    }
+    next
+}
+
+/// Repository the project root belongs to and the path of the project root in it,
+/// which is empty unless the project is a package of a monorepo
+fn open_repository(project_root: &str) -> Result<(Repository, PathBuf), failure::Error> {
+    let repo = Repository::discover(project_root)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("bare repositories have no changelog"))?
+        .canonicalize()?;
+    let scope = Path::new(project_root)
+        .canonicalize()?
+        .strip_prefix(&workdir)
+        .map(Path::to_owned)
+        .unwrap_or_default();
+
+    Ok((repo, scope))
+}
 
+/// Whether the commit changes anything in `scope`, compared to its first parent
+fn touches_path(commit: &Commit, scope: &Path) -> Result<bool, git2::Error> {
+    if scope.as_os_str().is_empty() {
+        return Ok(true);
+    }
+
+    // Trees are content-addressed, so the scope is changed if its tree id is
+    let scope_id = |commit: &Commit| -> Result<Option<git2::Oid>, git2::Error> {
+        Ok(commit.tree()?.get_path(scope).ok().map(|entry| entry.id()))
+    };
+    let parent_id = match commit.parents().next() {
+        Some(parent) => scope_id(&parent)?,
+        None => None,
+    };
+
+    Ok(scope_id(commit)? != parent_id)
+}
+
+/// Commits in `rev..HEAD` changing the project, newest first.
+/// Commits reverted in the range are left out along with their reverts.
+fn commits_since_rev<'r>(repo: &'r Repository, scope: &Path, rev: &str) -> Result<Vec<Commit<'r>>, failure::Error> {
+    let range = format!("{}..HEAD", rev);
     let mut walker = repo.revwalk()?;
+    walker.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME);
     walker.push_range(&range)?;
 
-    let bump = walker
-        .map(|c| {
-            repo.find_commit(c.expect("not a valid commit"))
-                .expect("no commit found")
-        })
+    let mut commits = Vec::new();
+    for oid in walker {
+        let commit = repo.find_commit(oid?)?;
+        if touches_path(&commit, scope)? {
+            commits.push(commit);
+        }
+    }
+
+    Ok(reverts::cancel_reverts(commits))
+}
+
+fn version_bump_since_rev(
+    path: &str,
+    rev: &str,
+    ignore: &[String],
+    rules: &RuleSet,
+) -> Result<CommitType, failure::Error> {
+    let (repo, scope) = open_repository(path)?;
+    log::debug!(
+        "analyzing commits {}..HEAD touching {:?} to determine version bump",
+        rev,
+        scope
+    );
+
+    let bumps = commits_since_rev(&repo, &scope, rev)?
+        .into_iter()
         .map(format_commit)
-        .map(|c| analyze_single(&c, ignore).expect("commit analysis failed"))
-        .max()
-        .unwrap_or(CommitType::Unknown);
+        .map(|c| analyze_single(&c, ignore, rules))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(bump)
+    Ok(bumps.into_iter().max().unwrap_or(CommitType::Unknown))
 }
 
 fn format_commit(commit: Commit) -> String {
     format!("{}\n{}", commit.id(), commit.message().unwrap_or(""))
 }
 
-#[derive(PartialEq, Eq, Debug, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, Debug, PartialOrd, Ord, Clone, Copy)]
 pub enum CommitType {
     Unknown,
     Patch,
@@ -292,93 +668,498 @@ pub enum CommitType {
     Major,
 }
 
-pub fn analyze_single(commit_str: &str, ignore: &[String]) -> Result<CommitType, failure::Error> {
+pub fn analyze_single(commit_str: &str, ignore: &[String], rules: &RuleSet) -> Result<CommitType, failure::Error> {
     use CommitType::*;
 
-    let message = commit_str.trim().split_terminator('\n').nth(1);
-
-    let clog = Clog::new().expect("Clog initialization failed");
-    let commit = clog.parse_raw_commit(commit_str);
+    let message = commit_str
+        .trim()
+        .split_once('\n')
+        .map(|(_, message)| message)
+        .unwrap_or_default();
+
+    let commit = match ConventionalCommit::parse(message) {
+        Ok(commit) => commit,
+        Err(err) => {
+            log::trace!("{}", err);
+            return Ok(Unknown);
+        }
+    };
 
-    if !commit.breaks.is_empty() {
+    if commit.is_breaking() {
         return Ok(Major);
     }
 
-    if ignore.contains(&commit.component.to_ascii_lowercase()) {
+    if (commit.scope.iter()).any(|scope| ignore.contains(&scope.to_ascii_lowercase())) {
         return Ok(Unknown);
     }
 
-    let commit_type = match &commit.commit_type[..] {
-        "Features" => Minor,
-        "Bug Fixes" => Patch,
-        _ => Unknown,
-    };
+    let commit_type = rules.release(&commit);
 
-    if let Some(message) = message {
-        log::trace!("derived commit type {:?} for {}", commit_type, message);
-    }
+    log::trace!(
+        "derived commit type {:?} for {}",
+        commit_type,
+        message.lines().next().unwrap_or_default()
+    );
 
     Ok(commit_type)
 }
 
 pub fn generate_changelog(
-    repository_path: &str,
+    project_root: &str,
     from_rev: &str,
     new_version: &semver::Version,
+    rules: &RuleSet,
+    linker: &IssueLinker,
 ) -> Result<String, failure::Error> {
-    log::debug!("generating changelog {}..{}", from_rev, new_version);
+    let changelog = render_changelog(project_root, from_rev, new_version, rules, linker)?;
 
-    let mut clog = Clog::with_dir(repository_path)?;
+    // Release notes go without the version header
+    match changelog.find('\n') {
+        Some(newline_offset) => Ok(changelog[newline_offset + 1..].into()),
+        None => Ok(changelog),
+    }
+}
 
+/// Changelog section of the version, made of the commits changing the project,
+/// grouped in the sections given by the release rules
+fn render_changelog(
+    project_root: &str,
+    from_rev: &str,
+    new_version: &semver::Version,
+    rules: &RuleSet,
+    linker: &IssueLinker,
+) -> Result<String, failure::Error> {
+    log::debug!("generating changelog {}..{}", from_rev, new_version);
+
+    let (repo, scope) = open_repository(project_root)?;
+    let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+    let mut clog = Clog::with_dirs(repo.path(), workdir)?;
     clog.from(from_rev).version(format!("v{}", new_version));
+    if let Some(repository) = linker.repository() {
+        clog.repository(repository);
+    }
+
+    let commits = conventional_commits_since_rev(&repo, &scope, from_rev, rules)?
+        .into_iter()
+        .filter_map(|(commit, conventional, section)| Some(changelog_entry(&commit, &conventional, &section?, linker)))
+        .collect();
 
     let mut out_buf = BufWriter::new(Vec::new());
 
     {
         let mut writer = MarkdownWriter::new(&mut out_buf);
-        clog.write_changelog_with(&mut writer)?
+        writer.write_changelog(&clog, &SectionMap::from_commits(commits))?;
     }
 
-    let out_buf = out_buf.into_inner().unwrap();
-    let changelog = String::from_utf8(out_buf).unwrap();
+    let out_buf = out_buf.into_inner()?;
+    Ok(String::from_utf8(out_buf)?)
+}
 
-    match changelog.find('\n') {
-        Some(newline_offset) => Ok(changelog[newline_offset + 1..].into()),
-        None => Ok(changelog),
+/// Conventional commits changing the project since `rev`, with their release notes section
+fn conventional_commits_since_rev<'r>(
+    repo: &'r Repository,
+    scope: &Path,
+    rev: &str,
+    rules: &RuleSet,
+) -> Result<Vec<(Commit<'r>, ConventionalCommit, Option<String>)>, failure::Error> {
+    let commits = commits_since_rev(repo, scope, rev)?
+        .into_iter()
+        .filter_map(|commit| {
+            let conventional = ConventionalCommit::parse(commit.message().unwrap_or_default()).ok()?;
+            let section = rules.section(&conventional).map(ToOwned::to_owned);
+            Some((commit, conventional, section))
+        })
+        .collect();
+
+    Ok(commits)
+}
+
+/// Changelog entry of the commit listed in the section.
+/// Issue numbers closed by the footers are listed by clog, the other references are linked in the subject.
+fn changelog_entry(
+    commit: &Commit,
+    conventional: &ConventionalCommit,
+    section: &str,
+    linker: &IssueLinker,
+) -> clog::git::Commit {
+    let references = linker.references(conventional);
+    let mut subject = linker.linkify(&conventional.description, &references);
+    let mut closes = Vec::new();
+    for reference in &references {
+        match reference.number() {
+            Some(number) if reference.closes => closes.push(number.to_owned()),
+            _ if conventional.description.contains(&reference.id) => (),
+            _ => subject.push_str(&format!(" {}", linker.linkify(&reference.id, &references))),
+        }
+    }
+    let breaks = if conventional.is_breaking() {
+        vec![String::new()]
+    } else {
+        vec![]
+    };
+
+    clog::git::Commit {
+        hash: commit.id().to_string(),
+        subject,
+        component: conventional.scope.clone().unwrap_or_default(),
+        closes,
+        breaks,
+        commit_type: section.to_owned(),
+    }
+}
+
+/// Keep a Changelog entry of the commit, e.g. `**scope:** description`
+fn keep_a_changelog_entry(commit: &ConventionalCommit, linker: &IssueLinker) -> String {
+    let mut entry = String::new();
+    if commit.is_breaking() {
+        entry.push_str("**BREAKING:** ");
+    }
+    if let Some(scope) = &commit.scope {
+        entry.push_str(&format!("**{}:** ", scope));
     }
+    entry.push_str(&linker.linkify(&commit.description, &linker.references(commit)));
+    entry
+}
+
+/// Today's date in UTC, `YYYY-MM-DD`
+fn today() -> Result<String, failure::Error> {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+    Ok(format_date(git2::Time::new(now.as_secs() as i64, 0)))
+}
+
+/// Data available to `notes_template` and provided as `release_notes_data`
+fn notes_context(
+    project_root: &str,
+    current_version: &Version,
+    new_version: &semver::Version,
+    rules: &RuleSet,
+    remote_url: Option<&str>,
+    linker: &IssueLinker,
+    changelog: bool,
+) -> Result<NotesContext, failure::Error> {
+    let (repo, scope) = open_repository(project_root)?;
+    let head = repo.head()?.peel_to_commit()?.id().to_string();
+
+    let mut entries: Vec<(String, CommitEntry)> = Vec::new();
+    let mut breaking_changes = Vec::new();
+    let mut authors: Vec<Author> = Vec::new();
+
+    for (commit, conventional, section) in conventional_commits_since_rev(&repo, &scope, &current_version.rev, rules)? {
+        let hash = commit.id().to_string();
+        let short_hash = hash.chars().take(7).collect::<String>();
+        let author = Author {
+            name: commit.author().name().unwrap_or_default().to_owned(),
+            email: commit.author().email().unwrap_or_default().to_owned(),
+        };
+
+        let breaking_note = conventional.breaking_note().map(ToOwned::to_owned);
+        if let Some(note) = &breaking_note {
+            breaking_changes.push(BreakingChange {
+                hash: hash.clone(),
+                short_hash: short_hash.clone(),
+                scope: conventional.scope.clone(),
+                description: conventional.description.clone(),
+                note: note.clone(),
+            });
+        }
+
+        // Commits left out of the notes are not credited either
+        let section = match section {
+            Some(section) => section,
+            None => continue,
+        };
+        if !authors.contains(&author) {
+            authors.push(author.clone());
+        }
+
+        let references = linker.references(&conventional);
+        let entry = CommitEntry {
+            linked_description: linker.linkify(&conventional.description, &references),
+            references,
+            breaking: conventional.is_breaking(),
+            breaking_note,
+            hash,
+            short_hash,
+            commit_type: conventional.commit_type,
+            scope: conventional.scope,
+            description: conventional.description,
+            body: conventional.body,
+            author,
+        };
+        entries.push((section, entry));
+    }
+
+    let sections = rules
+        .sections()
+        .into_iter()
+        .map(|title| {
+            let commits = (entries.iter())
+                .filter(|(section, _)| section == title)
+                .map(|(_, entry)| entry.clone())
+                .collect();
+            Section::new(title.to_owned(), commits)
+        })
+        .filter(|section| !section.commits.is_empty())
+        .collect();
+
+    let previous_version = current_version.semver.as_ref().map(ToString::to_string);
+    let compare_url = previous_version
+        .as_ref()
+        .and(remote_url)
+        .and_then(|remote_url| notes::compare_url(remote_url, &current_version.rev, &head));
+
+    Ok(NotesContext {
+        version: new_version.to_string(),
+        previous_version,
+        date: today()?,
+        changelog,
+        sections,
+        breaking_changes,
+        authors,
+        compare_url,
+    })
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(
+        display = "cannot release {} from a maintenance branch limited to {}: {} releases must be made from a branch with a higher range",
+        _0, _1, _2
+    )]
+    VersionOutOfRange(semver::Version, VersionRange, &'static str),
+    #[fail(display = "failed to read cfg.clog.notes_template {}: {}", _0, _1)]
+    NotesTemplateUnreadable(String, std::io::Error),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::TempDir;
 
     #[test]
     fn unknown_type() {
         let commit = "0\nThis commit message has no type";
-        assert_eq!(CommitType::Unknown, analyze_single(commit, &[]).unwrap());
+        assert_eq!(
+            CommitType::Unknown,
+            analyze_single(commit, &[], &RuleSet::default()).unwrap()
+        );
     }
 
     #[test]
     fn patch_commit() {
         let commit = "0\nfix: This commit fixes a bug";
-        assert_eq!(CommitType::Patch, analyze_single(commit, &[]).unwrap());
+        assert_eq!(
+            CommitType::Patch,
+            analyze_single(commit, &[], &RuleSet::default()).unwrap()
+        );
     }
 
     #[test]
     fn minor_commit() {
         let commit = "0\nfeat: This commit introduces a new feature";
-        assert_eq!(CommitType::Minor, analyze_single(commit, &[]).unwrap());
+        assert_eq!(
+            CommitType::Minor,
+            analyze_single(commit, &[], &RuleSet::default()).unwrap()
+        );
     }
 
     #[test]
     fn major_commit() {
         let commit = "0\nfeat: This commits breaks something\nBREAKING CHANGE: breaks things";
-        assert_eq!(CommitType::Major, analyze_single(commit, &[]).unwrap());
+        assert_eq!(
+            CommitType::Major,
+            analyze_single(commit, &[], &RuleSet::default()).unwrap()
+        );
     }
 
     #[test]
     fn ignored_component() {
         let commit = "0\nfeat(ci): This commits should be ignored";
-        assert_eq!(CommitType::Unknown, analyze_single(commit, &["ci".into()]).unwrap());
+        assert_eq!(
+            CommitType::Unknown,
+            analyze_single(commit, &["ci".into()], &RuleSet::default()).unwrap()
+        );
+    }
+
+    fn version(v: &str) -> semver::Version {
+        semver::Version::parse(v).unwrap()
+    }
+
+    fn next(current: Option<&str>, bump: CommitType, channel: Option<&str>) -> String {
+        next_version(current.map(version).as_ref(), bump, channel).to_string()
+    }
+
+    #[test]
+    fn next_stable_version() {
+        assert_eq!(next(None, CommitType::Major, None), "0.1.0");
+        assert_eq!(next(Some("1.2.3"), CommitType::Unknown, None), "1.2.3");
+        assert_eq!(next(Some("1.2.3"), CommitType::Patch, None), "1.2.4");
+        assert_eq!(next(Some("1.2.3"), CommitType::Minor, None), "1.3.0");
+        assert_eq!(next(Some("1.2.3"), CommitType::Major, None), "2.0.0");
+        assert_eq!(next(Some("0.2.3"), CommitType::Minor, None), "0.2.4");
+        assert_eq!(next(Some("0.2.3"), CommitType::Major, None), "0.3.0");
+    }
+
+    #[test]
+    fn next_prerelease_version() {
+        assert_eq!(next(None, CommitType::Major, Some("rc")), "0.1.0-rc.1");
+        assert_eq!(next(Some("1.2.3"), CommitType::Unknown, Some("rc")), "1.2.3");
+        assert_eq!(next(Some("1.2.3"), CommitType::Minor, Some("rc")), "1.3.0-rc.1");
+        assert_eq!(next(Some("1.3.0-rc.1"), CommitType::Patch, Some("rc")), "1.3.0-rc.2");
+        assert_eq!(next(Some("1.3.0-rc.2"), CommitType::Minor, Some("rc")), "1.3.0-rc.3");
+        assert_eq!(next(Some("1.3.0-rc.2"), CommitType::Major, Some("rc")), "2.0.0-rc.1");
+        assert_eq!(
+            next(Some("1.2.4-beta.1"), CommitType::Minor, Some("beta")),
+            "1.3.0-beta.1"
+        );
+        assert_eq!(
+            next(Some("2.0.0-beta"), CommitType::Major, Some("beta")),
+            "2.0.0-beta.1"
+        );
+    }
+
+    #[test]
+    fn maintenance_range() {
+        let check = |current: &str, bump: CommitType, range: &str| {
+            let range: VersionRange = serde_json::from_value(serde_json::json!(range)).unwrap();
+            let next = next_version(Some(&version(current)), bump, None);
+            check_range(Some(&version(current)), bump, &next, &range).map_err(|err| err.to_string())
+        };
+
+        assert!(check("1.2.3", CommitType::Minor, "1.x").is_ok());
+        assert!(check("1.2.3", CommitType::Patch, "1.2.x").is_ok());
+        assert!(check("0.2.3", CommitType::Major, "0.x").is_ok());
+
+        let err = check("1.2.3", CommitType::Minor, "1.2.x").unwrap_err();
+        assert!(err.starts_with("cannot release 1.3.0 from a maintenance branch limited to 1.2.x: minor releases"));
+        let err = check("1.2.3", CommitType::Major, "1.x").unwrap_err();
+        assert!(err.contains("major releases"));
+    }
+
+    #[test]
+    fn release_tags() {
+        let mut config = Config {
+            next_version: Value::with_value(NEXT_VERSION, version("2.0.0")),
+            ..Config::default()
+        };
+        assert_eq!(config.release_tag(), "v2.0.0");
+
+        config.release_tag = Value::with_value(RELEASE_TAG, "core-2.0.0".into());
+        assert_eq!(config.release_tag(), "core-2.0.0");
+    }
+
+    /// Commit writing the message to the file, returns the hash of the commit
+    fn commit_file(repo: &Repository, path: &str, message: &str) -> String {
+        let signature = git2::Signature::now("John Doe", "jd@example.com").unwrap();
+        let file = repo.workdir().unwrap().join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, message).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn monorepo_packages() {
+        let dir = TempDir::new("clog-monorepo");
+        let repo = Repository::init(&dir).unwrap();
+        let commit = |path: &str, message: &str| commit_file(&repo, path, message);
+
+        let init = commit("README.md", "chore: init");
+        commit("crates/a/lib.rs", "feat(a): new feature of a");
+        commit("crates/b/lib.rs", "fix(b): bug fix of b");
+
+        let rules = RuleSet::default();
+        let root = dir.display().to_string();
+        let a = dir.join("crates/a").display().to_string();
+        let b = dir.join("crates/b").display().to_string();
+
+        assert_eq!(
+            version_bump_since_rev(&root, &init, &[], &rules).unwrap(),
+            CommitType::Minor
+        );
+        assert_eq!(
+            version_bump_since_rev(&a, &init, &[], &rules).unwrap(),
+            CommitType::Minor
+        );
+        assert_eq!(
+            version_bump_since_rev(&b, &init, &[], &rules).unwrap(),
+            CommitType::Patch
+        );
+
+        let linker = IssueLinker::new("", None);
+        let notes = generate_changelog(&b, &init, &version("1.0.1"), &rules, &linker).unwrap();
+        assert!(notes.contains("bug fix of b"));
+        assert!(!notes.contains("new feature of a"));
+
+        let current = Version {
+            rev: init,
+            semver: Some(version("1.0.0")),
+        };
+        let context = notes_context(
+            &root,
+            &current,
+            &version("1.1.0"),
+            &rules,
+            Some("git@github.com:o/r.git"),
+            &IssueLinker::new("git@github.com:o/r.git", None),
+            true,
+        )
+        .unwrap();
+        assert_eq!(context.previous_version.as_deref(), Some("1.0.0"));
+        let titles: Vec<_> = context.sections.iter().map(|section| section.title.as_str()).collect();
+        assert_eq!(titles, vec!["Features", "Bug Fixes"]);
+        assert_eq!(context.sections[0].scopes[0].scope.as_deref(), Some("a"));
+        assert_eq!(context.authors.len(), 1);
+        assert!(context
+            .compare_url
+            .unwrap()
+            .starts_with(&format!("https://github.com/o/r/compare/{}...", current.rev)));
+
+        // Without the git plugin there's no remote to link to
+        let linker = IssueLinker::new("", None);
+        let context = notes_context(&root, &current, &version("1.1.0"), &rules, None, &linker, true).unwrap();
+        assert_eq!(context.compare_url, None);
+    }
+
+    #[test]
+    fn reverted_commits() {
+        let dir = TempDir::new("clog-reverts");
+        let repo = Repository::init(&dir).unwrap();
+        let commit = |path: &str, message: &str| commit_file(&repo, path, message);
+
+        let released_fix = commit("fix.rs", "fix: released fix");
+        let released = commit("README.md", "chore: release 1.0.0");
+        let feat = commit("feat.rs", "feat: short-lived feature");
+        commit(
+            "feat.rs",
+            &format!("Revert \"feat: short-lived feature\"\n\nThis reverts commit {}.", feat),
+        );
+
+        let rules = RuleSet::default();
+        let root = dir.display().to_string();
+        assert_eq!(
+            version_bump_since_rev(&root, &released, &[], &rules).unwrap(),
+            CommitType::Unknown
+        );
+
+        commit(
+            "fix.rs",
+            &format!("revert: released fix\n\nRefs: {}", &released_fix[..7]),
+        );
+        assert_eq!(
+            version_bump_since_rev(&root, &released, &[], &rules).unwrap(),
+            CommitType::Patch
+        );
+
+        let linker = IssueLinker::new("", None);
+        let notes = generate_changelog(&root, &released, &version("1.0.1"), &rules, &linker).unwrap();
+        assert!(notes.contains("released fix"));
+        assert!(!notes.contains("short-lived feature"));
     }
 }
@@ -0,0 +1,355 @@
+//! [Conventional Commits 1.0](https://www.conventionalcommits.org/en/v1.0.0/) commit message parser
+use failure::Fail;
+use regex::Regex;
+
+lazy_static::lazy_static! {
+    static ref HEADER: Regex =
+        Regex::new(r"^(?P<type>[^\s():!]+)(?:\((?P<scope>[^()\r\n]*)\))?(?P<breaking>!)?: (?P<description>.*)$").unwrap();
+    static ref GIT_REVERT: Regex = Regex::new(r#"^Revert "(?P<header>.+)"$"#).unwrap();
+    static ref FOOTER: Regex = Regex::new(r"^(?P<token>BREAKING CHANGE|[\w-]+)(?::\s|\s#)(?P<value>.*)$").unwrap();
+}
+
+/// `<type>[(scope)][!]: <description>`, followed by an optional body and footers
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConventionalCommit {
+    /// Type as written, types are compared case-insensitively
+    pub commit_type: String,
+    pub scope: Option<String>,
+    /// `!` right before the colon
+    pub exclamation_mark: bool,
+    pub description: String,
+    /// Free-form paragraphs between the header and the footers
+    pub body: Option<String>,
+    pub footers: Vec<Footer>,
+}
+
+/// `Token: value` or `Token #value`, git trailers are footers too
+#[derive(Debug, Clone, PartialEq)]
+pub struct Footer {
+    pub token: String,
+    /// Value after the separator, `#` separators are kept, e.g. `#123` for `Closes #123`
+    pub value: String,
+}
+
+impl Footer {
+    pub fn is_breaking_change(&self) -> bool {
+        self.token == "BREAKING CHANGE" || self.token == "BREAKING-CHANGE"
+    }
+}
+
+impl ConventionalCommit {
+    pub fn parse(message: &str) -> Result<Self, Error> {
+        let message = message.trim().replace("\r\n", "\n");
+        let mut lines = message.lines();
+
+        let header = lines.next().unwrap_or_default();
+        let (commit_type, scope, exclamation_mark, description) = match HEADER.captures(header) {
+            Some(caps) => (
+                caps["type"].to_owned(),
+                caps.name("scope").map(|scope| scope.as_str().trim().to_owned()),
+                caps.name("breaking").is_some(),
+                caps["description"].trim().to_owned(),
+            ),
+            // Commits made by `git revert` are `revert` commits described by the reverted header
+            None => match GIT_REVERT.captures(header) {
+                Some(caps) => ("revert".to_owned(), None, false, caps["header"].to_owned()),
+                None => return Err(Error::NotConventional(header.to_owned())),
+            },
+        };
+        if description.is_empty() {
+            return Err(Error::NotConventional(header.to_owned()));
+        }
+
+        let rest: Vec<&str> = lines.collect();
+        let paragraphs = paragraphs(&rest);
+        // Footers are the trailing paragraphs made of footers only, everything before them is the body
+        let footers_start = (0..paragraphs.len())
+            .find(|&i| paragraphs[i..].iter().all(|p| FOOTER.is_match(p[0])))
+            .unwrap_or(paragraphs.len());
+
+        let body = paragraphs[..footers_start]
+            .iter()
+            .map(|p| p.join("\n"))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let mut footers: Vec<Footer> = Vec::new();
+        for line in paragraphs[footers_start..].iter().flatten() {
+            match FOOTER.captures(line) {
+                Some(caps) => footers.push(Footer {
+                    token: caps["token"].to_owned(),
+                    value: footer_value(line, &caps),
+                }),
+                // A footer value may span several lines of its paragraph
+                None => {
+                    let footer = footers.last_mut().expect("footer paragraphs start with a footer");
+                    footer.value.push('\n');
+                    footer.value.push_str(line);
+                }
+            }
+        }
+
+        Ok(ConventionalCommit {
+            commit_type,
+            scope,
+            exclamation_mark,
+            description,
+            body: if body.is_empty() { None } else { Some(body) },
+            footers,
+        })
+    }
+
+    /// Breaking changes are marked with `!` or a `BREAKING CHANGE` footer
+    pub fn is_breaking(&self) -> bool {
+        self.exclamation_mark || self.footers.iter().any(Footer::is_breaking_change)
+    }
+
+    /// Description of the breaking change: the `BREAKING CHANGE` footer, or the description
+    /// of commits marked with `!` only, `None` if the commit is not a breaking change
+    pub fn breaking_note(&self) -> Option<&str> {
+        match self.footers.iter().find(|footer| footer.is_breaking_change()) {
+            Some(footer) => Some(&footer.value),
+            None if self.exclamation_mark => Some(&self.description),
+            None => None,
+        }
+    }
+
+    /// Footers formatted as `Token: value`
+    pub fn footer_lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.footers
+            .iter()
+            .map(|footer| format!("{}: {}", footer.token, footer.value))
+    }
+}
+
+fn footer_value(line: &str, caps: &regex::Captures) -> String {
+    let value = caps["value"].trim_end();
+    let token_end = caps.name("token").map_or(0, |token| token.end());
+    let value_start = caps.name("value").map_or(line.len(), |value| value.start());
+    let separator = &line[token_end..value_start];
+    if separator.ends_with('#') {
+        format!("#{}", value)
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Non-empty paragraphs of the lines, separated by blank lines
+fn paragraphs<'a>(lines: &[&'a str]) -> Vec<Vec<&'a str>> {
+    lines
+        .split(|line| line.trim().is_empty())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.iter().map(|line| line.trim_end()).collect())
+        .collect()
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "not a conventional commit: {:?}", _0)]
+    NotConventional(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(message: &str) -> ConventionalCommit {
+        ConventionalCommit::parse(message).unwrap()
+    }
+
+    fn footer(token: &str, value: &str) -> Footer {
+        Footer {
+            token: token.into(),
+            value: value.into(),
+        }
+    }
+
+    // Examples from the specification
+
+    #[test]
+    fn breaking_change_footer() {
+        let commit = parse(
+            "feat: allow provided config object to extend other configs\n\n\
+             BREAKING CHANGE: `extends` key in config file is now used for extending other config files",
+        );
+        assert_eq!(commit.commit_type, "feat");
+        assert_eq!(commit.scope, None);
+        assert_eq!(
+            commit.description,
+            "allow provided config object to extend other configs"
+        );
+        assert_eq!(commit.body, None);
+        assert_eq!(
+            commit.footers,
+            vec![footer(
+                "BREAKING CHANGE",
+                "`extends` key in config file is now used for extending other config files"
+            )]
+        );
+        assert!(commit.is_breaking());
+    }
+
+    #[test]
+    fn exclamation_mark() {
+        let commit = parse("feat!: send an email to the customer when a product is shipped");
+        assert!(commit.exclamation_mark);
+        assert!(commit.is_breaking());
+        assert_eq!(
+            commit.description,
+            "send an email to the customer when a product is shipped"
+        );
+        assert_eq!(commit.breaking_note(), Some(commit.description.as_str()));
+
+        let commit = parse("feat(api)!: send an email to the customer when a product is shipped");
+        assert_eq!(commit.scope.as_deref(), Some("api"));
+        assert!(commit.is_breaking());
+    }
+
+    #[test]
+    fn exclamation_mark_and_footer() {
+        let commit = parse(
+            "chore!: drop support for Node 6\n\nBREAKING CHANGE: use JavaScript features not available in Node 6.",
+        );
+        assert_eq!(commit.commit_type, "chore");
+        assert!(commit.exclamation_mark);
+        assert_eq!(
+            commit.breaking_note(),
+            Some("use JavaScript features not available in Node 6.")
+        );
+        assert_eq!(
+            commit.footers,
+            vec![footer(
+                "BREAKING CHANGE",
+                "use JavaScript features not available in Node 6."
+            )]
+        );
+    }
+
+    #[test]
+    fn no_body() {
+        let commit = parse("docs: correct spelling of CHANGELOG");
+        assert_eq!(commit.commit_type, "docs");
+        assert_eq!(commit.body, None);
+        assert!(commit.footers.is_empty());
+        assert!(!commit.is_breaking());
+    }
+
+    #[test]
+    fn scope() {
+        let commit = parse("feat(lang): add Polish language");
+        assert_eq!(commit.scope.as_deref(), Some("lang"));
+        assert_eq!(commit.description, "add Polish language");
+    }
+
+    #[test]
+    fn multi_paragraph_body_and_footers() {
+        let commit = parse(
+            "fix: prevent racing of requests\n\
+             \n\
+             Introduce a request id and a reference to latest request. Dismiss\n\
+             incoming responses other than from latest request.\n\
+             \n\
+             Remove timeouts which were used to mitigate the racing issue but are\n\
+             obsolete now.\n\
+             \n\
+             Reviewed-by: Z\n\
+             Refs: #123\n",
+        );
+        assert_eq!(
+            commit.body.as_deref(),
+            Some(
+                "Introduce a request id and a reference to latest request. Dismiss\n\
+                 incoming responses other than from latest request.\n\
+                 \n\
+                 Remove timeouts which were used to mitigate the racing issue but are\n\
+                 obsolete now."
+            )
+        );
+        assert_eq!(commit.footers, vec![footer("Reviewed-by", "Z"), footer("Refs", "#123")]);
+    }
+
+    // Other cases
+
+    #[test]
+    fn breaking_change_synonym() {
+        let commit = parse("fix: new config format\n\nBREAKING-CHANGE: old configs are rejected");
+        assert!(commit.is_breaking());
+        assert!(!parse("fix: breaking the loop\n\nThis is not a breaking change").is_breaking());
+    }
+
+    #[test]
+    fn trailers_and_hash_separator() {
+        let commit = parse(
+            "fix(parser): handle empty input\n\n\
+             Closes #12\n\
+             Signed-off-by: John Doe <jd@example.com>\n\
+             Co-authored-by: Jane Doe <jane@example.com>",
+        );
+        assert_eq!(
+            commit.footers,
+            vec![
+                footer("Closes", "#12"),
+                footer("Signed-off-by", "John Doe <jd@example.com>"),
+                footer("Co-authored-by", "Jane Doe <jane@example.com>"),
+            ]
+        );
+        assert_eq!(commit.footer_lines().next().as_deref(), Some("Closes: #12"));
+    }
+
+    #[test]
+    fn multi_line_footer_value() {
+        let commit = parse(
+            "feat: new API\n\n\
+             BREAKING CHANGE: the old API is removed,\n\
+             use the new one instead\n\
+             Refs: #7",
+        );
+        assert_eq!(
+            commit.footers,
+            vec![
+                footer("BREAKING CHANGE", "the old API is removed,\nuse the new one instead"),
+                footer("Refs", "#7"),
+            ]
+        );
+    }
+
+    #[test]
+    fn body_paragraph_looking_like_a_footer() {
+        let commit = parse("feat: thing\n\nNote: this is the body\n\nIt goes on.\n\nRefs: #1");
+        assert_eq!(commit.body.as_deref(), Some("Note: this is the body\n\nIt goes on."));
+        assert_eq!(commit.footers, vec![footer("Refs", "#1")]);
+    }
+
+    #[test]
+    fn case_insensitive_type_is_kept_as_written() {
+        assert_eq!(parse("FEAT: shout").commit_type, "FEAT");
+        assert_eq!(parse("Fix(Core): fix").scope.as_deref(), Some("Core"));
+    }
+
+    #[test]
+    fn git_revert() {
+        let commit = parse("Revert \"feat!: new API\"\n\nThis reverts commit 0a1b2c3d.");
+        assert_eq!(commit.commit_type, "revert");
+        assert_eq!(commit.description, "feat!: new API");
+        assert_eq!(commit.body.as_deref(), Some("This reverts commit 0a1b2c3d."));
+        assert!(!commit.is_breaking());
+    }
+
+    #[test]
+    fn not_conventional() {
+        for message in &[
+            "Update README",
+            "feat add something",
+            "feat:no space",
+            "feat: ",
+            "feat (scope): space before scope",
+            "Merge branch 'master' into feature",
+        ] {
+            assert!(
+                ConventionalCommit::parse(message).is_err(),
+                "{:?} must be rejected",
+                message
+            );
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Changelog files in the [Keep a Changelog](https://keepachangelog.com/en/1.1.0/) format
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+lazy_static::lazy_static! {
+    static ref UNRELEASED: Regex = Regex::new(r"(?i)^##\s+\[?unreleased\]?\s*$").unwrap();
+    static ref UNRELEASED_LINK: Regex = Regex::new(r"(?i)^\[unreleased\]:").unwrap();
+    static ref LINK: Regex = Regex::new(r"^\[[^\]]+\]:\s*\S").unwrap();
+}
+
+const HEADER: &str = "# Changelog
+
+All notable changes to this project will be documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/),
+and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).
+";
+
+/// Kind of change, sections of a release are listed in this order
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Added,
+    Changed,
+    Deprecated,
+    Removed,
+    Fixed,
+    Security,
+}
+
+impl Category {
+    const ALL: [Category; 6] = [
+        Category::Added,
+        Category::Changed,
+        Category::Deprecated,
+        Category::Removed,
+        Category::Fixed,
+        Category::Security,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            Category::Added => "Added",
+            Category::Changed => "Changed",
+            Category::Deprecated => "Deprecated",
+            Category::Removed => "Removed",
+            Category::Fixed => "Fixed",
+            Category::Security => "Security",
+        }
+    }
+
+    /// Category of the commits of the type, unless `cfg.clog.changelog_categories` says otherwise
+    pub fn of_type(commit_type: &str) -> Option<Self> {
+        let category = match commit_type.to_ascii_lowercase().as_str() {
+            "feat" | "ft" => Category::Added,
+            "fix" | "fx" => Category::Fixed,
+            "perf" | "refactor" => Category::Changed,
+            "deprecate" => Category::Deprecated,
+            "remove" => Category::Removed,
+            "security" => Category::Security,
+            _ => return None,
+        };
+        Some(category)
+    }
+}
+
+/// Section of a version to insert into the changelog
+pub struct Release {
+    pub version: String,
+    pub date: String,
+    pub entries: Vec<(Category, String)>,
+    pub links: Option<Links>,
+}
+
+/// Link references of the `[Unreleased]` and the released version headings
+pub struct Links {
+    pub unreleased: String,
+    pub release: String,
+}
+
+/// Changelog with the release inserted right under `## [Unreleased]`.
+///
+/// Entries written by hand in the Unreleased section are moved into the release, the rest of the file is kept as is,
+/// except for the `[Unreleased]` link reference which is updated to start at the release.
+pub fn insert_release(changelog: &str, release: &Release) -> String {
+    let changelog = if changelog.trim().is_empty() { HEADER } else { changelog };
+    let lines: Vec<&str> = changelog.lines().collect();
+    let is_end_of_section = |line: &&str| line.starts_with("## ") || LINK.is_match(line);
+
+    let (before, unreleased, after) = match lines.iter().position(|line| UNRELEASED.is_match(line)) {
+        Some(heading) => {
+            let end = lines[heading + 1..]
+                .iter()
+                .position(is_end_of_section)
+                .map_or(lines.len(), |end| heading + 1 + end);
+            (&lines[..heading], &lines[heading + 1..end], &lines[end..])
+        }
+        None => {
+            let start = lines.iter().position(is_end_of_section).unwrap_or(lines.len());
+            (&lines[..start], &[][..], &lines[start..])
+        }
+    };
+
+    let mut out: Vec<String> = trim_blank_lines(before).iter().map(|&line| line.to_owned()).collect();
+    out.push(String::new());
+    out.push("## [Unreleased]".into());
+    out.push(String::new());
+    out.push(format!("## [{}] - {}", release.version, release.date));
+    out.push(String::new());
+    out.extend(release_body(unreleased, &release.entries));
+    if !after.is_empty() {
+        out.push(String::new());
+        out.extend(after.iter().map(|&line| line.to_owned()));
+    }
+
+    if let Some(links) = &release.links {
+        update_links(&mut out, &release.version, links);
+    }
+
+    let mut out = trim_blank_lines(&out).join("\n");
+    out.push('\n');
+    out
+}
+
+/// Hand-written Unreleased content merged with the generated entries, grouped by category
+fn release_body(unreleased: &[&str], entries: &[(Category, String)]) -> Vec<String> {
+    let mut text: Vec<String> = Vec::new();
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+
+    for &line in unreleased {
+        match line.strip_prefix("### ") {
+            Some(title) => groups.push((title.trim().to_owned(), Vec::new())),
+            None => match groups.last_mut() {
+                Some((_, lines)) => lines.push(line.to_owned()),
+                None => text.push(line.to_owned()),
+            },
+        }
+    }
+
+    for (category, entry) in entries {
+        let line = format!("- {}", entry);
+        match groups
+            .iter_mut()
+            .find(|(title, _)| title.eq_ignore_ascii_case(category.title()))
+        {
+            Some((_, lines)) => {
+                let lines_end = lines.len() - lines.iter().rev().take_while(|line| line.trim().is_empty()).count();
+                lines.insert(lines_end, line);
+            }
+            None => groups.push((category.title().to_owned(), vec![line])),
+        }
+    }
+
+    // Sections unknown to the format keep their place after the known ones
+    groups.sort_by_key(|(title, _)| {
+        Category::ALL
+            .iter()
+            .position(|category| category.title().eq_ignore_ascii_case(title))
+            .unwrap_or(Category::ALL.len())
+    });
+
+    let mut body = trim_blank_lines(&text).to_vec();
+    for (title, lines) in groups {
+        if !body.is_empty() {
+            body.push(String::new());
+        }
+        body.push(format!("### {}", title));
+        body.push(String::new());
+        body.extend(trim_blank_lines(&lines).iter().cloned());
+    }
+    body
+}
+
+fn update_links(lines: &mut Vec<String>, version: &str, links: &Links) {
+    let unreleased = format!("[Unreleased]: {}", links.unreleased);
+    let release = format!("[{}]: {}", version, links.release);
+
+    match lines.iter().position(|line| UNRELEASED_LINK.is_match(line)) {
+        Some(index) => {
+            lines[index] = unreleased;
+            lines.insert(index + 1, release);
+        }
+        None => match lines.iter().position(|line| LINK.is_match(line)) {
+            Some(index) => {
+                lines.insert(index, unreleased);
+                lines.insert(index + 1, release);
+            }
+            None => {
+                lines.push(String::new());
+                lines.push(unreleased);
+                lines.push(release);
+            }
+        },
+    }
+}
+
+fn trim_blank_lines<T: AsRef<str>>(lines: &[T]) -> &[T] {
+    let start = lines
+        .iter()
+        .position(|line| !line.as_ref().trim().is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|line| !line.as_ref().trim().is_empty())
+        .map_or(start, |end| end + 1);
+    &lines[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(links: bool) -> Release {
+        Release {
+            version: "1.1.0".into(),
+            date: "2019-10-02".into(),
+            entries: vec![
+                (Category::Added, "**ui:** dark mode".into()),
+                (Category::Fixed, "crash on start".into()),
+            ],
+            links: if links {
+                Some(Links {
+                    unreleased: "https://github.com/o/r/compare/v1.1.0...HEAD".into(),
+                    release: "https://github.com/o/r/compare/v1.0.0...v1.1.0".into(),
+                })
+            } else {
+                None
+            },
+        }
+    }
+
+    #[test]
+    fn insert_into_existing_changelog() {
+        let changelog = "# Changelog\n\
+                         \n\
+                         ## [Unreleased]\n\
+                         ### Fixed\n\
+                         - typo in the docs\n\
+                         \n\
+                         ### Changed\n\
+                         - new logo\n\
+                         \n\
+                         ## [1.0.0] - 2019-01-01\n\
+                         ### Added\n\
+                         - everything\n\
+                         \n\
+                         [Unreleased]: https://github.com/o/r/compare/v1.0.0...HEAD\n\
+                         [1.0.0]: https://github.com/o/r/releases/tag/v1.0.0\n";
+
+        assert_eq!(
+            insert_release(changelog, &release(true)),
+            "# Changelog\n\
+             \n\
+             ## [Unreleased]\n\
+             \n\
+             ## [1.1.0] - 2019-10-02\n\
+             \n\
+             ### Added\n\
+             \n\
+             - **ui:** dark mode\n\
+             \n\
+             ### Changed\n\
+             \n\
+             - new logo\n\
+             \n\
+             ### Fixed\n\
+             \n\
+             - typo in the docs\n\
+             - crash on start\n\
+             \n\
+             ## [1.0.0] - 2019-01-01\n\
+             ### Added\n\
+             - everything\n\
+             \n\
+             [Unreleased]: https://github.com/o/r/compare/v1.1.0...HEAD\n\
+             [1.1.0]: https://github.com/o/r/compare/v1.0.0...v1.1.0\n\
+             [1.0.0]: https://github.com/o/r/releases/tag/v1.0.0\n"
+        );
+    }
+
+    #[test]
+    fn insert_into_new_changelog() {
+        let changelog = insert_release("", &release(true));
+        assert!(changelog.starts_with(HEADER));
+        assert!(changelog.contains("\n## [Unreleased]\n\n## [1.1.0] - 2019-10-02\n\n### Added\n"));
+        assert!(changelog.ends_with(
+            "- crash on start\n\
+             \n\
+             [Unreleased]: https://github.com/o/r/compare/v1.1.0...HEAD\n\
+             [1.1.0]: https://github.com/o/r/compare/v1.0.0...v1.1.0\n"
+        ));
+    }
+
+    #[test]
+    fn insert_without_unreleased_section() {
+        let changelog = "# Changelog\n\n## [1.0.0] - 2019-01-01\n- everything\n";
+        assert_eq!(
+            insert_release(changelog, &release(false)),
+            "# Changelog\n\
+             \n\
+             ## [Unreleased]\n\
+             \n\
+             ## [1.1.0] - 2019-10-02\n\
+             \n\
+             ### Added\n\
+             \n\
+             - **ui:** dark mode\n\
+             \n\
+             ### Fixed\n\
+             \n\
+             - crash on start\n\
+             \n\
+             ## [1.0.0] - 2019-01-01\n\
+             - everything\n"
+        );
+    }
+
+    #[test]
+    fn categories_of_types() {
+        assert_eq!(Category::of_type("feat"), Some(Category::Added));
+        assert_eq!(Category::of_type("Fix"), Some(Category::Fixed));
+        assert_eq!(Category::of_type("refactor"), Some(Category::Changed));
+        assert_eq!(Category::of_type("docs"), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! Release notes rendered from the `cfg.clog.notes_template` handlebars template
+use failure::Fail;
+use handlebars::{Handlebars, Template};
+use serde::Serialize;
+
+use super::references::Reference;
+use crate::builtin_plugins::git::url::to_https;
+
+/// Data available to the template, also provided as `release_notes_data`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct NotesContext {
+    pub version: String,
+    /// `null` for the first release
+    pub previous_version: Option<String>,
+    /// Release date, `YYYY-MM-DD`
+    pub date: String,
+    /// `true` when rendering the changelog file, `false` for the release notes
+    pub changelog: bool,
+    /// Non-empty sections, in the order of the release rules
+    pub sections: Vec<Section>,
+    pub breaking_changes: Vec<BreakingChange>,
+    /// Authors of the commits, in order of appearance
+    pub authors: Vec<Author>,
+    /// Web page comparing the previous release with the released commit, `null` for the first release
+    /// or if the remote URL can't be turned into an HTTPS one
+    pub compare_url: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub commits: Vec<CommitEntry>,
+    /// Commits of the section grouped by scope, commits without a scope go first
+    pub scopes: Vec<ScopeGroup>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ScopeGroup {
+    pub scope: Option<String>,
+    pub commits: Vec<CommitEntry>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CommitEntry {
+    pub hash: String,
+    pub short_hash: String,
+    #[serde(rename = "type")]
+    pub commit_type: String,
+    pub scope: Option<String>,
+    pub description: String,
+    /// Description with the references turned into markdown links
+    pub linked_description: String,
+    pub body: Option<String>,
+    pub breaking: bool,
+    /// Note of the breaking change, see `BreakingChange::note`
+    pub breaking_note: Option<String>,
+    /// Issues and pull requests referenced by the commit
+    pub references: Vec<Reference>,
+    pub author: Author,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BreakingChange {
+    pub hash: String,
+    pub short_hash: String,
+    pub scope: Option<String>,
+    pub description: String,
+    /// Value of the `BREAKING CHANGE` footer, the description for commits marked with `!` only
+    pub note: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Author {
+    pub name: String,
+    pub email: String,
+}
+
+impl Section {
+    pub fn new(title: String, commits: Vec<CommitEntry>) -> Self {
+        let mut scopes: Vec<ScopeGroup> = Vec::new();
+        for commit in &commits {
+            match scopes.iter_mut().find(|group| group.scope == commit.scope) {
+                Some(group) => group.commits.push(commit.clone()),
+                None => scopes.push(ScopeGroup {
+                    scope: commit.scope.clone(),
+                    commits: vec![commit.clone()],
+                }),
+            }
+        }
+        scopes.sort_by_key(|group| group.scope.is_some());
+
+        Section { title, commits, scopes }
+    }
+}
+
+/// Check that the template compiles
+pub fn validate_template(path: &str, template: &str) -> Result<(), Error> {
+    Template::compile(template)
+        .map(|_| ())
+        .map_err(|err| Error::InvalidTemplate(path.to_owned(), err.to_string()))
+}
+
+/// Render the template, values are inserted as they are, without HTML escaping
+pub fn render(path: &str, template: &str, context: &NotesContext) -> Result<String, Error> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars.set_strict_mode(true);
+
+    handlebars
+        .render_template(template, context)
+        .map_err(|err| Error::InvalidTemplate(path.to_owned(), err.to_string()))
+}
+
+/// Web page of the repository, if the remote is reachable over HTTPS
+pub fn repository_url(remote_url: &str) -> Option<String> {
+    let https = if remote_url.starts_with("https://") {
+        remote_url.to_owned()
+    } else {
+        to_https(remote_url, &[]).ok()??
+    };
+
+    Some(https.trim_end_matches('/').trim_end_matches(".git").to_owned())
+}
+
+/// `<repository>/compare/<from>...<to>` page of the remote, if it is reachable over HTTPS
+pub fn compare_url(remote_url: &str, from: &str, to: &str) -> Option<String> {
+    repository_url(remote_url).map(|repository| format!("{}/compare/{}...{}", repository, from, to))
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "failed to render cfg.clog.notes_template {}: {}", _0, _1)]
+    InvalidTemplate(String, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(hash: &str, scope: Option<&str>, description: &str) -> CommitEntry {
+        CommitEntry {
+            hash: hash.into(),
+            short_hash: hash[..2].into(),
+            commit_type: "feat".into(),
+            scope: scope.map(Into::into),
+            description: description.into(),
+            linked_description: description.into(),
+            body: None,
+            breaking: false,
+            breaking_note: None,
+            references: vec![],
+            author: Author {
+                name: "John Doe".into(),
+                email: "jd@example.com".into(),
+            },
+        }
+    }
+
+    #[test]
+    fn render_template() {
+        let section = Section::new(
+            "Features".into(),
+            vec![
+                commit("aaaa", Some("ui"), "dark mode"),
+                commit("bbbb", None, "<plugins>"),
+                commit("cccc", Some("ui"), "bigger buttons"),
+            ],
+        );
+        assert_eq!(section.scopes.len(), 2);
+        assert_eq!(section.scopes[0].scope, None);
+        assert_eq!(section.scopes[1].commits.len(), 2);
+
+        let context = NotesContext {
+            version: "1.3.0".into(),
+            previous_version: Some("1.2.0".into()),
+            date: "2019-10-02".into(),
+            changelog: false,
+            sections: vec![section],
+            breaking_changes: vec![],
+            authors: vec![],
+            compare_url: compare_url("git@github.com:etclabscore/semantic-rs.git", "v1.2.0", "abcd"),
+        };
+
+        let template = "## {{version}} ({{date}})\n\
+                        {{#each sections}}### {{title}}\n\
+                        {{#each scopes}}{{#each commits}}* {{#if scope}}**{{scope}}:** {{/if}}{{description}} ({{short_hash}})\n\
+                        {{/each}}{{/each}}{{/each}}\
+                        [Full diff]({{compare_url}})";
+        assert_eq!(
+            render("notes.hbs", template, &context).unwrap(),
+            "## 1.3.0 (2019-10-02)\n\
+             ### Features\n\
+             * <plugins> (bb)\n\
+             * **ui:** dark mode (aa)\n\
+             * **ui:** bigger buttons (cc)\n\
+             [Full diff](https://github.com/etclabscore/semantic-rs/compare/v1.2.0...abcd)"
+        );
+
+        assert!(validate_template("notes.hbs", "{{#each sections}}").is_err());
+        assert!(render("notes.hbs", "{{unknown}}", &context).is_err());
+    }
+
+    #[test]
+    fn compare_urls() {
+        assert_eq!(
+            compare_url("https://gitlab.com/group/project.git", "v1.0.0", "abcd").as_deref(),
+            Some("https://gitlab.com/group/project/compare/v1.0.0...abcd")
+        );
+        assert_eq!(compare_url("/srv/git/project.git", "v1.0.0", "abcd"), None);
+    }
+}
@@ -0,0 +1,258 @@
+//! Issues and pull requests referenced by the commits, e.g. `fix: crash on empty input (#123)` or `Closes: #45, JIRA-991`
+use regex::{Captures, Regex};
+use serde::Serialize;
+
+use super::conventional::ConventionalCommit;
+use crate::builtin_plugins::github::user_repo_from_url;
+
+lazy_static::lazy_static! {
+    /// `#123` or a Jira-style `ABC-123` key
+    static ref REFERENCE: Regex = Regex::new(r"#\d+\b|\b[A-Z][A-Z0-9_]+-\d+\b").unwrap();
+    static ref PARENTHESES: Regex = Regex::new(r"\([^()]*\)").unwrap();
+}
+
+/// Footers closing the issues they reference, see GitHub's closing keywords
+const CLOSING_TOKENS: &[&str] = &[
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+const REFERENCING_TOKENS: &[&str] = &["ref", "refs", "references", "see", "related", "related-to"];
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Reference {
+    /// `#123` or `ABC-123`
+    pub id: String,
+    /// Whether the commit closes the issue, i.e. the issue is referenced by a closing footer
+    pub closes: bool,
+    pub url: Option<String>,
+}
+
+impl Reference {
+    /// Number of the issue or pull request, `None` for Jira-style keys
+    pub fn number(&self) -> Option<&str> {
+        self.id.strip_prefix('#')
+    }
+}
+
+/// Issue referenced by the commits of the release, provided as `referenced_issues`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReferencedIssue {
+    pub id: String,
+    /// Whether any of the commits closes the issue
+    pub closes: bool,
+    pub url: Option<String>,
+    /// Hashes of the commits referencing the issue
+    pub commits: Vec<String>,
+}
+
+/// References of the commits, identified by their hashes, merged by issue
+pub fn referenced_issues<'a>(references: impl IntoIterator<Item = (&'a str, &'a Reference)>) -> Vec<ReferencedIssue> {
+    let mut issues: Vec<ReferencedIssue> = Vec::new();
+    for (hash, reference) in references {
+        match issues.iter_mut().find(|issue| issue.id == reference.id) {
+            Some(issue) => {
+                issue.closes |= reference.closes;
+                issue.commits.push(hash.to_owned());
+            }
+            None => issues.push(ReferencedIssue {
+                id: reference.id.clone(),
+                closes: reference.closes,
+                url: reference.url.clone(),
+                commits: vec![hash.to_owned()],
+            }),
+        }
+    }
+    issues
+}
+
+/// Turns the references into links
+pub struct IssueLinker {
+    /// `https://github.com/<user>/<repo>`, from the git remote
+    repository: Option<String>,
+    /// `cfg.clog.issue_url`, URL of the Jira-style keys with an `{id}` placeholder
+    key_url: Option<String>,
+}
+
+impl IssueLinker {
+    pub fn new(remote_url: &str, key_url: Option<&str>) -> Self {
+        let repository = user_repo_from_url(remote_url)
+            .ok()
+            .map(|(user, repo)| format!("https://github.com/{}/{}", user, repo));
+
+        IssueLinker {
+            repository,
+            key_url: key_url.map(ToOwned::to_owned),
+        }
+    }
+
+    /// GitHub repository the issue numbers refer to
+    pub fn repository(&self) -> Option<&str> {
+        self.repository.as_deref()
+    }
+
+    pub fn url(&self, id: &str) -> Option<String> {
+        match id.strip_prefix('#') {
+            Some(number) => Some(format!("{}/issues/{}", self.repository.as_ref()?, number)),
+            None => Some(self.key_url.as_ref()?.replace("{id}", id)),
+        }
+    }
+
+    /// References of the commit: issue numbers anywhere in the description, Jira-style keys in parentheses
+    /// of the description, and everything referenced by the `Closes`, `Fixes`, `Refs` and similar footers
+    pub fn references(&self, commit: &ConventionalCommit) -> Vec<Reference> {
+        let mut references: Vec<Reference> = Vec::new();
+        let mut add = |id: &str, closes: bool| match references.iter_mut().find(|reference| reference.id == id) {
+            Some(reference) => reference.closes |= closes,
+            None => references.push(Reference {
+                id: id.to_owned(),
+                closes,
+                url: self.url(id),
+            }),
+        };
+
+        for id in description_references(&commit.description) {
+            add(id, false);
+        }
+
+        for footer in &commit.footers {
+            let token = footer.token.to_ascii_lowercase();
+            let closes = CLOSING_TOKENS.contains(&token.as_str());
+            if !closes && !REFERENCING_TOKENS.contains(&token.as_str()) {
+                continue;
+            }
+
+            for id in REFERENCE.find_iter(&footer.value) {
+                add(id.as_str(), closes);
+            }
+        }
+
+        references
+    }
+
+    /// Text with the references turned into markdown links
+    pub fn linkify(&self, text: &str, references: &[Reference]) -> String {
+        let link = |caps: &Captures| {
+            let id = &caps[0];
+            let reference = references.iter().find(|reference| reference.id == id);
+            match reference.and_then(|reference| reference.url.as_ref()) {
+                Some(url) => format!("[{}]({})", id, url),
+                None => id.to_owned(),
+            }
+        };
+
+        REFERENCE.replace_all(text, link).into_owned()
+    }
+}
+
+fn description_references(description: &str) -> Vec<&str> {
+    let mut ids: Vec<&str> = REFERENCE
+        .find_iter(description)
+        .map(|id| id.as_str())
+        .filter(|id| id.starts_with('#'))
+        .collect();
+
+    let keys = PARENTHESES
+        .find_iter(description)
+        .flat_map(|parentheses| REFERENCE.find_iter(parentheses.as_str()))
+        .map(|id| id.as_str())
+        .filter(|id| !id.starts_with('#'));
+    for key in keys {
+        if !ids.contains(&key) {
+            ids.push(key);
+        }
+    }
+
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(id: &str, closes: bool, url: Option<&str>) -> Reference {
+        Reference {
+            id: id.into(),
+            closes,
+            url: url.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn extract_references() {
+        let linker = IssueLinker::new(
+            "git@github.com:etclabscore/semantic-rs.git",
+            Some("https://jira.example.com/browse/{id}"),
+        );
+        let commit = ConventionalCommit::parse(
+            "fix: crash on empty UTF-8 input (#123, CORE-7)\n\n\
+             Closes: #45, JIRA-991\n\
+             Refs: #123\n\
+             Reviewed-by: #nobody-1",
+        )
+        .unwrap();
+
+        let references = linker.references(&commit);
+        assert_eq!(
+            references,
+            vec![
+                reference(
+                    "#123",
+                    false,
+                    Some("https://github.com/etclabscore/semantic-rs/issues/123")
+                ),
+                reference("CORE-7", false, Some("https://jira.example.com/browse/CORE-7")),
+                reference(
+                    "#45",
+                    true,
+                    Some("https://github.com/etclabscore/semantic-rs/issues/45")
+                ),
+                reference("JIRA-991", true, Some("https://jira.example.com/browse/JIRA-991")),
+            ]
+        );
+        assert_eq!(references[2].number(), Some("45"));
+
+        assert_eq!(
+            linker.linkify(&commit.description, &references),
+            "crash on empty UTF-8 input \
+             ([#123](https://github.com/etclabscore/semantic-rs/issues/123), \
+             [CORE-7](https://jira.example.com/browse/CORE-7))"
+        );
+    }
+
+    #[test]
+    fn references_without_urls() {
+        let linker = IssueLinker::new("/srv/git/repo", None);
+        let commit = ConventionalCommit::parse("feat: thing (#1)\n\nFixes: ABC-2").unwrap();
+
+        let references = linker.references(&commit);
+        assert_eq!(
+            references,
+            vec![reference("#1", false, None), reference("ABC-2", true, None)]
+        );
+        assert_eq!(linker.linkify(&commit.description, &references), "thing (#1)");
+    }
+
+    #[test]
+    fn merge_referenced_issues() {
+        let first = [reference("#1", false, None), reference("ABC-2", true, None)];
+        let second = [reference("#1", true, None)];
+        let references = (first.iter().map(|r| ("a", r))).chain(second.iter().map(|r| ("b", r)));
+
+        assert_eq!(
+            referenced_issues(references),
+            vec![
+                ReferencedIssue {
+                    id: "#1".into(),
+                    closes: true,
+                    url: None,
+                    commits: vec!["a".into(), "b".into()],
+                },
+                ReferencedIssue {
+                    id: "ABC-2".into(),
+                    closes: true,
+                    url: None,
+                    commits: vec!["a".into()],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Reverted commits cancel out with their reverts inside a release
+use git2::Commit;
+use regex::Regex;
+
+use super::conventional::ConventionalCommit;
+
+lazy_static::lazy_static! {
+    static ref REVERTS_COMMIT: Regex = Regex::new(r"(?im)^This reverts commit ([0-9a-f]{7,40})\b").unwrap();
+    static ref HASH: Regex = Regex::new(r"^(?i)[0-9a-f]{7,40}$").unwrap();
+}
+
+/// Hash of the commit reverted by the message, possibly abbreviated: the `This reverts commit <hash>` line
+/// written by `git revert`, or a `Refs: <hash>` footer of a `revert:` commit
+pub fn reverted_commit(message: &str) -> Option<String> {
+    if let Some(caps) = REVERTS_COMMIT.captures(message) {
+        return Some(caps[1].to_ascii_lowercase());
+    }
+
+    let commit = ConventionalCommit::parse(message).ok()?;
+    if !commit.commit_type.eq_ignore_ascii_case("revert") {
+        return None;
+    }
+
+    (commit.footers.iter())
+        .filter(|footer| footer.token.eq_ignore_ascii_case("refs"))
+        .flat_map(|footer| footer.value.split(','))
+        .map(str::trim)
+        .find(|hash| HASH.is_match(hash))
+        .map(str::to_ascii_lowercase)
+}
+
+/// Commits, newest first, without the commits reverted by a later commit of the list and their reverts.
+/// A revert of a revert restores the originally reverted commit.
+pub fn cancel_reverts(commits: Vec<Commit>) -> Vec<Commit> {
+    let ids: Vec<String> = commits.iter().map(|commit| commit.id().to_string()).collect();
+    let mut cancelled = vec![false; commits.len()];
+
+    for (i, commit) in commits.iter().enumerate() {
+        if cancelled[i] {
+            continue;
+        }
+
+        let reverted = match reverted_commit(commit.message().unwrap_or_default()) {
+            Some(reverted) => reverted,
+            None => continue,
+        };
+
+        // Reverts of commits released before are left as they are
+        if let Some(j) = (i + 1..ids.len()).find(|&j| ids[j].starts_with(&reverted)) {
+            log::debug!("commit {} is reverted by {}, skipping both", ids[j], ids[i]);
+            cancelled[i] = true;
+            cancelled[j] = true;
+        }
+    }
+
+    commits
+        .into_iter()
+        .zip(cancelled)
+        .filter_map(|(commit, cancelled)| if cancelled { None } else { Some(commit) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverted_commits() {
+        assert_eq!(
+            reverted_commit("Revert \"feat: thing\"\n\nThis reverts commit 0A1B2C3D4E.").as_deref(),
+            Some("0a1b2c3d4e")
+        );
+        assert_eq!(
+            reverted_commit("revert: thing\n\nRefs: 0a1b2c3").as_deref(),
+            Some("0a1b2c3")
+        );
+        assert_eq!(reverted_commit("revert: thing\n\nRefs: #12"), None);
+        assert_eq!(reverted_commit("fix: thing\n\nRefs: 0a1b2c3"), None);
+        assert_eq!(reverted_commit("feat: thing"), None);
+    }
+}
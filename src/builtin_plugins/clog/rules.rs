@@ -0,0 +1,267 @@
+//! Mapping of commits to version bumps and changelog sections
+use failure::Fail;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::conventional::ConventionalCommit;
+use super::CommitType;
+
+/// `cfg.clog.release_rules` entry: commits matching every condition set in the rule
+/// make a `release` and are listed in the `section` of the release notes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReleaseRule {
+    #[serde(default, rename = "type")]
+    pub commit_type: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// Regex matched against the description
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// Regex matched against every footer, as `Token: value`
+    #[serde(default)]
+    pub footer: Option<String>,
+    pub release: Release,
+    #[serde(default)]
+    pub section: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Release {
+    Major,
+    Minor,
+    Patch,
+    None,
+}
+
+impl From<Release> for CommitType {
+    fn from(release: Release) -> Self {
+        match release {
+            Release::Major => CommitType::Major,
+            Release::Minor => CommitType::Minor,
+            Release::Patch => CommitType::Patch,
+            Release::None => CommitType::Unknown,
+        }
+    }
+}
+
+struct CompiledRule {
+    rule: ReleaseRule,
+    subject: Option<Regex>,
+    footer: Option<Regex>,
+}
+
+impl CompiledRule {
+    fn new(rule: ReleaseRule) -> Result<Self, Error> {
+        let compile = |pattern: &Option<String>| {
+            pattern
+                .as_ref()
+                .map(|pattern| Regex::new(pattern).map_err(|err| Error::InvalidPattern(pattern.clone(), err)))
+                .transpose()
+        };
+
+        Ok(CompiledRule {
+            subject: compile(&rule.subject)?,
+            footer: compile(&rule.footer)?,
+            rule,
+        })
+    }
+
+    fn matches(&self, commit: &ConventionalCommit) -> bool {
+        let eq = |expected: &Option<String>, actual: &str| {
+            expected.iter().all(|expected| expected.eq_ignore_ascii_case(actual))
+        };
+
+        eq(&self.rule.commit_type, &commit.commit_type)
+            && eq(&self.rule.scope, commit.scope.as_deref().unwrap_or_default())
+            && self.subject.iter().all(|regex| regex.is_match(&commit.description))
+            && self
+                .footer
+                .iter()
+                .all(|regex| commit.footer_lines().any(|line| regex.is_match(&line)))
+    }
+}
+
+/// User rules followed by the default ones, the first matching rule wins
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+impl RuleSet {
+    pub fn new(rules: &[ReleaseRule]) -> Result<Self, Error> {
+        let rules = rules
+            .iter()
+            .cloned()
+            .chain(default_rules())
+            .map(CompiledRule::new)
+            .collect::<Result<_, _>>()?;
+
+        Ok(RuleSet { rules })
+    }
+
+    /// Version bump the commit calls for
+    pub fn release(&self, commit: &ConventionalCommit) -> CommitType {
+        self.find(commit)
+            .map(|rule| rule.release.into())
+            .unwrap_or(CommitType::Unknown)
+    }
+
+    /// Release notes section of the commit, `None` if the commit is not worth mentioning.
+    /// Rules without a section fall back to the section of the first rule for the commit type.
+    pub fn section(&self, commit: &ConventionalCommit) -> Option<&str> {
+        let rule = self.find(commit)?;
+        let same_type = |rule: &&CompiledRule| {
+            (rule.rule.commit_type.iter()).any(|commit_type| commit_type.eq_ignore_ascii_case(&commit.commit_type))
+        };
+
+        rule.section.as_deref().or_else(|| {
+            self.rules
+                .iter()
+                .filter(same_type)
+                .find_map(|rule| rule.rule.section.as_deref())
+        })
+    }
+
+    /// Release notes sections, in the order of the rules
+    pub fn sections(&self) -> Vec<&str> {
+        let mut sections: Vec<&str> = Vec::new();
+        for section in self.rules.iter().filter_map(|rule| rule.rule.section.as_deref()) {
+            if !sections.contains(&section) {
+                sections.push(section);
+            }
+        }
+        sections
+    }
+
+    fn find(&self, commit: &ConventionalCommit) -> Option<&ReleaseRule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(commit))
+            .map(|rule| &rule.rule)
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet::new(&[]).expect("default rules are valid")
+    }
+}
+
+/// Conventional Commits types released by default, with the aliases understood by clog
+fn default_rules() -> Vec<ReleaseRule> {
+    let rule = |commit_type: &str, release, section: &str| ReleaseRule {
+        commit_type: Some(commit_type.into()),
+        scope: None,
+        subject: None,
+        footer: None,
+        release,
+        section: Some(section.into()),
+    };
+
+    vec![
+        rule("feat", Release::Minor, "Features"),
+        rule("ft", Release::Minor, "Features"),
+        rule("fix", Release::Patch, "Bug Fixes"),
+        rule("fx", Release::Patch, "Bug Fixes"),
+        rule("perf", Release::None, "Performance"),
+        rule("revert", Release::Patch, "Reverts"),
+    ]
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "invalid cfg.clog.release_rules pattern {:?}: {}", _0, _1)]
+    InvalidPattern(String, regex::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> RuleSet {
+        #[derive(Deserialize)]
+        struct Rules {
+            release_rules: Vec<ReleaseRule>,
+        }
+
+        let rules: Rules = toml::from_str(toml).unwrap();
+        RuleSet::new(&rules.release_rules).unwrap()
+    }
+
+    fn commit(message: &str) -> ConventionalCommit {
+        ConventionalCommit::parse(message).unwrap()
+    }
+
+    #[test]
+    fn default_release_rules() {
+        let rules = RuleSet::default();
+        let release = |message| rules.release(&commit(message));
+
+        assert_eq!(release("feat: feature"), CommitType::Minor);
+        assert_eq!(release("fix(ui): fix"), CommitType::Patch);
+        assert_eq!(release("perf: faster"), CommitType::Unknown);
+        assert_eq!(release("docs: docs"), CommitType::Unknown);
+        assert_eq!(rules.section(&commit("perf: faster")), Some("Performance"));
+        assert_eq!(rules.section(&commit("docs: docs")), None);
+        assert_eq!(release("revert: feature"), CommitType::Patch);
+        assert_eq!(
+            rules.sections(),
+            vec!["Features", "Bug Fixes", "Performance", "Reverts"]
+        );
+    }
+
+    #[test]
+    fn custom_release_rules() {
+        let rules = rules(
+            r#"
+            [[release_rules]]
+            type = "perf"
+            release = "patch"
+
+            [[release_rules]]
+            type = "refactor"
+            scope = "api"
+            release = "patch"
+            section = "Refactoring"
+
+            [[release_rules]]
+            type = "docs"
+            scope = "readme"
+            release = "none"
+
+            [[release_rules]]
+            subject = "^hotfix"
+            release = "patch"
+
+            [[release_rules]]
+            footer = "^Release-As: minor$"
+            release = "minor"
+            "#,
+        );
+        let release = |message| rules.release(&commit(message));
+        let section = |message| rules.section(&commit(message));
+
+        assert_eq!(release("perf: faster"), CommitType::Patch);
+        assert_eq!(section("perf: faster"), Some("Performance"));
+        assert_eq!(release("refactor(api): cleanup"), CommitType::Patch);
+        assert_eq!(section("refactor(api): cleanup"), Some("Refactoring"));
+        assert_eq!(release("refactor(ui): cleanup"), CommitType::Unknown);
+        assert_eq!(release("docs(readme): typo"), CommitType::Unknown);
+        assert_eq!(section("docs(readme): typo"), None);
+        assert_eq!(release("chore: hotfix the build"), CommitType::Patch);
+        assert_eq!(release("build: hotfix the build"), CommitType::Patch);
+        assert_eq!(release("docs: the hotfix"), CommitType::Unknown);
+        assert_eq!(release("chore: bump\n\nRelease-As: minor"), CommitType::Minor);
+        assert_eq!(release("feat: still a feature"), CommitType::Minor);
+
+        let invalid = vec![ReleaseRule {
+            commit_type: None,
+            scope: None,
+            subject: Some("(".into()),
+            footer: None,
+            release: Release::Patch,
+            section: None,
+        }];
+        assert!(RuleSet::new(&invalid).is_err());
+    }
+}
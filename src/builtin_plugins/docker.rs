@@ -4,7 +4,7 @@ use std::path::PathBuf;
 use failure::Fail;
 
 use crate::plugin_support::command::PipedCommand;
-use crate::plugin_support::flow::{FlowError, Value};
+use crate::plugin_support::flow::{FlowError, MergeStrategy, Value};
 use crate::plugin_support::keys::NEXT_VERSION;
 use crate::plugin_support::proto::response::{self, PluginResponse};
 use crate::plugin_support::{PluginInterface, PluginStep};
@@ -34,7 +34,10 @@ struct Config {
 impl Default for Config {
     fn default() -> Self {
         Config {
-            next_version: Value::required_at(NEXT_VERSION, PluginStep::Publish),
+            next_version: Value::builder(NEXT_VERSION)
+                .required_at(PluginStep::Publish)
+                .merge(MergeStrategy::MaxSemver)
+                .build(),
             images: Value::with_default_value("images"),
             docker_user: Value::load_from_env("DOCKER_USER"),
             docker_password: Value::load_from_env("DOCKER_PASSWORD"),
@@ -59,6 +62,13 @@ enum Registry {
 
 struct State {
     credentials: Option<Credentials>,
+    /// Version tags pushed by the publish step as (image, tag), kept to be able to roll them back
+    pushed: Vec<(Image, String)>,
+}
+
+#[derive(Deserialize)]
+struct DockerhubToken {
+    token: String,
 }
 
 struct Credentials {
@@ -131,20 +141,25 @@ impl PluginInterface for DockerPlugin {
             response.warning("credentials are undefined, publishing will fail");
         }
 
-        self.state.replace(State { credentials });
+        self.state.replace(State {
+            credentials,
+            pushed: Vec::new(),
+        });
 
         response.body(())
     }
 
     fn publish(&mut self) -> response::Null {
         let config = &self.config;
-        let state = self.state.as_ref().ok_or(Error::MissingState)?;
+        let state = self.state.as_mut().ok_or(Error::MissingState)?;
 
         let credentials = state.credentials.as_ref().ok_or(Error::CredentialsUndefined)?;
 
         let version = config.next_version.as_value();
         let version = format!("{}", version);
 
+        // Pushed version tags are recorded right away, so a failure on a later image still rolls them back.
+        // The floating tag, e.g. `latest`, existed before the release, so it's never deleted on rollback.
         for image in config.images.as_value() {
             let registry_url = match image.registry {
                 Registry::Dockerhub => None,
@@ -159,13 +174,41 @@ impl PluginInterface for DockerPlugin {
             tag_image(&from, &get_image_path(image, &image.tag))?;
             tag_image(&from, &get_image_path(image, &version))?;
 
-            // Publish namespace/name/tag and namespace/name/version
-            push_image(image, &image.tag)?;
+            // Publish namespace/name/version and namespace/name/tag
             push_image(image, &version)?;
+            state.pushed.push((image.clone(), version.clone()));
+            push_image(image, &image.tag)?;
         }
 
         PluginResponse::from_ok(())
     }
+
+    fn rollback(&mut self) -> response::Null {
+        let mut response = PluginResponse::builder();
+
+        let state = match self.state.as_mut() {
+            Some(state) => state,
+            None => return response.body(()),
+        };
+
+        let credentials = state.credentials.as_ref().ok_or(Error::CredentialsUndefined)?;
+
+        for (image, tag) in state.pushed.drain(..) {
+            let path = get_image_path(&image, &tag);
+            let result = match image.registry {
+                Registry::Dockerhub => {
+                    let namespace = image.namespace.as_ref().unwrap_or(&credentials.username);
+                    dockerhub_delete_tag(credentials, namespace, &image.name, &tag)
+                }
+            };
+
+            if let Err(err) = result {
+                response.warning(format!("failed to untag image {}: {}", path, err));
+            }
+        }
+
+        response.body(())
+    }
 }
 
 fn get_image_path(image: &Image, tag: &str) -> String {
@@ -223,6 +266,40 @@ fn push_image(image: &Image, tag: &str) -> Result<(), failure::Error> {
     PipedCommand::new("docker", &["push", &path]).join(log::Level::Info)
 }
 
+fn dockerhub_delete_tag(
+    credentials: &Credentials,
+    namespace: &str,
+    name: &str,
+    tag: &str,
+) -> Result<(), failure::Error> {
+    log::info!("Deleting tag {}/{}:{} from DockerHub", namespace, name, tag);
+
+    let client = reqwest::Client::new();
+
+    let login = serde_json::json!({
+        "username": credentials.username,
+        "password": credentials.password,
+    });
+    let token: DockerhubToken = client
+        .post("https://hub.docker.com/v2/users/login/")
+        .json(&login)
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    let endpoint = format!(
+        "https://hub.docker.com/v2/repositories/{}/{}/tags/{}/",
+        namespace, name, tag
+    );
+    client
+        .delete(&endpoint)
+        .header("Authorization", format!("JWT {}", token.token))
+        .send()?
+        .error_for_status()?;
+
+    Ok(())
+}
+
 #[derive(Fail, Debug)]
 enum Error {
     #[fail(display = "DOCKER_USER or DOCKER_PASSWORD are not set, cannot push the image.")]
@@ -2,7 +2,7 @@ use failure::Fail;
 use serde::{Deserialize, Serialize};
 use std::ops::Try;
 
-use crate::plugin_support::flow::Value;
+use crate::plugin_support::flow::{MergeStrategy, Value};
 use crate::plugin_support::keys::{CURRENT_VERSION, NEXT_VERSION};
 use crate::plugin_support::proto::{
     response::{self, PluginResponse},
@@ -35,6 +35,7 @@ impl Default for Config {
             next_version: Value::builder(NEXT_VERSION)
                 .required_at(PluginStep::DeriveNextVersion)
                 .protected()
+                .merge(MergeStrategy::MaxSemver)
                 .build(),
         }
     }
@@ -1,18 +1,31 @@
 use std::env;
 use std::ops::Try;
 
+mod auth;
+mod sign;
+pub mod url;
+
 use failure::Fail;
-use git2::{self, Cred, Oid, PushOptions, RemoteCallbacks, Repository, Signature};
+use git2::{
+    self, AutotagOption, Direction, FetchOptions, Oid, PushOptions, Repository, ResetType, Signature, StatusOptions,
+};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-use crate::plugin_support::flow::{Availability, FlowError, ProvisionCapability, Value};
+use crate::config::Map;
+use crate::plugin_support::flow::{Availability, FlowError, MergeStrategy, ProvisionCapability, Value};
 use crate::plugin_support::keys::{
-    CURRENT_VERSION, FILES_TO_COMMIT, GIT_BRANCH, GIT_REMOTE, GIT_REMOTE_URL, NEXT_VERSION, PROJECT_ROOT, RELEASE_NOTES,
+    CURRENT_VERSION, FILES_TO_COMMIT, GIT_BRANCH, GIT_REMOTE, GIT_REMOTE_URL, NEXT_VERSION, PACKAGE, PROJECT_ROOT,
+    RELEASE_CHANNEL, RELEASE_NOTES, RELEASE_RANGE, RELEASE_TAG,
 };
 use crate::plugin_support::proto::response::{self, PluginResponse, PluginResponseBuilder};
-use crate::plugin_support::proto::{GitRevision, Version};
+use crate::plugin_support::proto::{GitRevision, Version, VersionRange};
 use crate::plugin_support::{PluginInterface, PluginStep};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use self::auth::{is_https_remote, Auth};
+use self::sign::{SignFormat, Signer};
+use self::url::UrlRewrite;
 
 pub struct GitPlugin {
     config: Config,
@@ -22,7 +35,22 @@ pub struct GitPlugin {
 struct State {
     repo: Repository,
     signature: Signature<'static>,
+    signer: Option<Signer>,
+    /// Pre-release identifier of the branch being released, `None` for the stable branch
+    channel: Option<String>,
+    /// Versions the maintenance branch being released is limited to, `None` for other branches
+    range: Option<VersionRange>,
     current_version: Option<Version>,
+    release: Option<Release>,
+}
+
+/// Changes made by the commit step, recorded as soon as they are made to be able to roll them back
+struct Release {
+    previous_head: Oid,
+    /// Release tag, once it is created
+    tag_name: Option<String>,
+    /// Whether the commit and the tag were pushed
+    pushed: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,9 +61,50 @@ struct Config {
     remote: Value<String>,
     force_https: Value<bool>,
     project_root: Value<String>,
+    /// Name of the monorepo package being released
+    package: Value<Option<String>>,
     next_version: Value<semver::Version>,
     files_to_commit: Value<Vec<String>>,
     changelog: Value<String>,
+    commit_message: Value<String>,
+    tag_format: Value<String>,
+    sign: Value<Option<SignFormat>>,
+    signing_key: Value<Option<String>>,
+    checks: Value<Checks>,
+    auth: Value<Auth>,
+    url_rewrites: Value<Vec<UrlRewrite>>,
+    /// Branches released as pre-releases, by branch name, with their pre-release identifiers
+    prerelease_branches: Value<Map<String, String>>,
+    /// Maintenance branches, by branch name, with the range of versions released from them
+    maintenance_branches: Value<Map<String, VersionRange>>,
+}
+
+/// Repository sanity checks, every check is enabled by default
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct Checks {
+    /// HEAD is on `cfg.git.branch`
+    branch: bool,
+    /// HEAD is not detached
+    detached_head: bool,
+    /// Working tree has no changes apart from ignored files
+    clean: bool,
+    /// Local branch is not behind its remote-tracking ref
+    up_to_date: bool,
+    /// Release tag exists neither locally nor on the remote, checked on Verify Release
+    tag: bool,
+}
+
+impl Default for Checks {
+    fn default() -> Self {
+        Checks {
+            branch: true,
+            detached_head: true,
+            clean: true,
+            up_to_date: true,
+            tag: true,
+        }
+    }
 }
 
 impl Default for Config {
@@ -47,18 +116,46 @@ impl Default for Config {
             remote: Value::with_value("remote", default_remote()),
             force_https: Value::with_default_value("force_https"),
             project_root: Value::protected(PROJECT_ROOT),
+            package: Value::protected(PACKAGE),
             next_version: Value::builder(NEXT_VERSION)
                 .protected()
-                .required_at(PluginStep::Commit)
+                .required_at(PluginStep::VerifyRelease)
+                .merge(MergeStrategy::MaxSemver)
                 .build(),
             files_to_commit: Value::builder(FILES_TO_COMMIT)
                 .protected()
                 .required_at(PluginStep::Commit)
+                .merge(MergeStrategy::ConcatUnique)
                 .build(),
             changelog: Value::builder(RELEASE_NOTES)
                 .protected()
-                .required_at(PluginStep::Commit)
+                .required_at(PluginStep::VerifyRelease)
                 .build(),
+            commit_message: Value::with_value("commit_message", default_commit_message()),
+            tag_format: Value::with_value("tag_format", default_tag_format()),
+            sign: Value::with_default_value("sign"),
+            signing_key: Value::with_default_value("signing_key"),
+            checks: Value::with_default_value("checks"),
+            auth: Value::with_default_value("auth"),
+            url_rewrites: Value::with_default_value("url_rewrites"),
+            prerelease_branches: Value::with_default_value("prerelease_branches"),
+            maintenance_branches: Value::with_default_value("maintenance_branches"),
+        }
+    }
+}
+
+impl Config {
+    fn package(&self) -> &str {
+        self.package.as_value().as_deref().unwrap_or_default()
+    }
+
+    /// Tags of monorepo packages are prefixed with the package name, unless `tag_format` places it itself
+    fn tag_format(&self) -> String {
+        let tag_format = self.tag_format.as_value();
+        if self.package.as_value().is_some() && !tag_format.contains("{package}") {
+            format!("{{package}}-{}", tag_format)
+        } else {
+            tag_format.clone()
         }
     }
 }
@@ -71,13 +168,145 @@ fn default_remote() -> String {
     "origin".into()
 }
 
+fn default_commit_message() -> String {
+    "chore(release): Version {version} [skip ci]".into()
+}
+
+fn default_tag_format() -> String {
+    "v{version}".into()
+}
+
+/// Values of the placeholders available in `commit_message` and `tag_format`
+struct TemplateVars {
+    version: String,
+    package: String,
+    previous_version: String,
+    branch: String,
+    date: String,
+    release_notes: String,
+}
+
+impl TemplateVars {
+    fn get(&self, placeholder: &str) -> Option<&str> {
+        let value = match placeholder {
+            "version" => &self.version,
+            "package" => &self.package,
+            "previous_version" => &self.previous_version,
+            "branch" => &self.branch,
+            "date" => &self.date,
+            "release_notes" => &self.release_notes,
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// Substitute `{placeholder}`s in the template
+fn render_template(template: &str, vars: &TemplateVars) -> Result<String, Error> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| Error::MalformedTemplate(template.to_owned()))?;
+        let placeholder = &rest[start + 1..end];
+        let value = vars
+            .get(placeholder)
+            .ok_or_else(|| Error::UnknownPlaceholder(placeholder.to_owned(), template.to_owned()))?;
+
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(value);
+        rest = &rest[end + 1..];
+    }
+
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Regex matching the tags made with `tag_format`, with the version captured as `version`
+///
+/// A `v` right before `{version}` is optional, so that tags like `1.2.3` are matched by `v{version}`.
+/// `{package}` only matches the tags of the package being released.
+/// `{release_notes}` is not allowed, as the release tag is provided to other plugins before the notes are ready.
+fn tag_pattern(tag_format: &str, package: &str) -> Result<Regex, Error> {
+    if tag_format.matches("{version}").count() != 1 {
+        return Err(Error::TagFormatWithoutVersion(tag_format.to_owned()));
+    }
+
+    let mut pattern = String::from("^");
+    let mut rest = tag_format;
+
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| Error::MalformedTemplate(tag_format.to_owned()))?;
+        let placeholder = &rest[start + 1..end];
+
+        let literal = &rest[..start];
+        match placeholder {
+            "version" if literal.ends_with('v') => {
+                pattern.push_str(&regex::escape(&literal[..literal.len() - 1]));
+                pattern.push_str("v?(?P<version>.+?)");
+            }
+            "version" => {
+                pattern.push_str(&regex::escape(literal));
+                pattern.push_str("(?P<version>.+?)");
+            }
+            "package" => {
+                pattern.push_str(&regex::escape(literal));
+                pattern.push_str(&regex::escape(package));
+            }
+            "previous_version" | "branch" | "date" => {
+                pattern.push_str(&regex::escape(literal));
+                pattern.push_str(".*?");
+            }
+            "release_notes" => return Err(Error::TagFormatWithReleaseNotes(tag_format.to_owned())),
+            other => return Err(Error::UnknownPlaceholder(other.to_owned(), tag_format.to_owned())),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    pattern.push_str(&regex::escape(rest));
+    pattern.push('$');
+
+    Ok(Regex::new(&pattern).expect("tag pattern is built from escaped literals"))
+}
+
+/// Format the time as `YYYY-MM-DD` in its own timezone
+pub fn format_date(time: git2::Time) -> String {
+    let seconds = time.seconds() + i64::from(time.offset_minutes()) * 60;
+    let days = seconds.div_euclid(86_400);
+
+    // Civil date from days since the Unix epoch, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
 impl State {
     pub fn new(config: &Config, repo: Repository) -> Result<Self, failure::Error> {
         let signature = Self::get_signature(&config, &repo)?;
+        let signer = Self::get_signer(config, &repo)?;
         Ok(State {
             repo,
             signature,
+            signer,
+            channel: None,
+            range: None,
             current_version: None,
+            release: None,
         })
     }
 
@@ -121,25 +350,157 @@ impl State {
         Ok(Signature::now(&author, &email)?)
     }
 
-    pub fn perform_pre_flight_checks<T>(&self, config: &Config, response: &mut PluginResponseBuilder<T>) {
-        let result = || -> Result<(), failure::Error> {
-            let remote = self.repo.find_remote(&config.remote.as_value())?;
-            let remote_url = remote.url().ok_or(Error::GitRemoteUndefined)?;
+    pub fn get_signer(cfg: &Config, repo: &Repository) -> Result<Option<Signer>, failure::Error> {
+        let format = match cfg.sign.as_value() {
+            Some(format) => *format,
+            None => return Ok(None),
+        };
 
-            if !config.force_https.as_value() && is_https_remote(remote_url) {
-                response.warnings(&[
-                    "Git remote is not HTTPS and 'cfg.git.force_https' != true:",
-                    "The publishing will fail if your environment doesn't hold your git ssh keys",
-                    "Consider setting 'cfg.git.force_https = true', that's most likely what you want if you're using GH_TOKEN authentication",
-                ]);
+        let key = match cfg.signing_key.as_value().clone() {
+            Some(key) => Some(key),
+            None => repo.config()?.get_string("user.signingkey").ok(),
+        };
+
+        Ok(Some(Signer::new(format, key)?))
+    }
+
+    pub fn perform_pre_flight_checks<T>(
+        &self,
+        config: &Config,
+        response: &mut PluginResponseBuilder<T>,
+    ) -> Result<(), failure::Error> {
+        let remote = self.repo.find_remote(config.remote.as_value())?;
+        let remote_url = remote.url().ok_or(Error::GitRemoteUndefined)?;
+
+        if *config.auth.as_value() == Auth::Default && !config.force_https.as_value() && !is_https_remote(remote_url) {
+            response.warnings(&[
+                "Git remote is not HTTPS and 'cfg.git.force_https' != true:",
+                "The publishing will fail if your environment doesn't hold your git ssh keys",
+                "Consider setting 'cfg.git.force_https = true', that's most likely what you want if you're using GH_TOKEN authentication",
+                "or configure the credentials in 'cfg.git.auth'",
+            ]);
+        }
+
+        let checks = config.checks.as_value();
+        let branch = config.branch.as_value();
+        let mut failed = Vec::new();
+
+        let is_detached = self.repo.head_detached()?;
+        if checks.detached_head && is_detached {
+            failed.push(("detached_head", "HEAD is detached".to_owned()));
+        }
+
+        if checks.branch && !is_detached {
+            let head = self.repo.head()?;
+            let head = head.shorthand().unwrap_or_default();
+            if head != branch {
+                failed.push((
+                    "branch",
+                    format!("HEAD is on branch {:?}, but the release branch is {:?}", head, branch),
+                ));
             }
+        }
 
-            Ok(())
-        }();
+        if checks.clean {
+            let changed = self.changed_files()?;
+            if !changed.is_empty() {
+                failed.push((
+                    "clean",
+                    format!("working tree has uncommitted changes: {}", changed.join(", ")),
+                ));
+            }
+        }
 
-        if let Err(err) = result {
-            response.error(err);
+        if checks.up_to_date {
+            let tracking_ref = format!("refs/remotes/{}/{}", config.remote.as_value(), branch);
+            match self.repo.refname_to_id(&tracking_ref) {
+                Ok(upstream) => {
+                    let local = self.repo.refname_to_id(&format!("refs/heads/{}", branch))?;
+                    let (_, behind) = self.repo.graph_ahead_behind(local, upstream)?;
+                    if behind > 0 {
+                        failed.push((
+                            "up_to_date",
+                            format!("branch {:?} is {} commit(s) behind {}", branch, behind, tracking_ref),
+                        ));
+                    }
+                }
+                Err(_) => {
+                    response.warning(format!(
+                        "{} not found, cannot check if {:?} is up to date",
+                        tracking_ref, branch
+                    ));
+                }
+            }
         }
+
+        checks_result(failed)
+    }
+
+    /// Check that the release tag exists neither locally nor on the remote
+    fn perform_tag_check<T>(
+        &self,
+        config: &Config,
+        response: &mut PluginResponseBuilder<T>,
+    ) -> Result<(), failure::Error> {
+        if !config.checks.as_value().tag {
+            return Ok(());
+        }
+
+        let tag_name = self.tag_name(config)?;
+        let tag_ref = format!("refs/tags/{}", tag_name);
+        let mut failed = Vec::new();
+
+        if self.repo.find_reference(&tag_ref).is_ok() {
+            failed.push(("tag", format!("tag {:?} already exists", tag_name)));
+        } else {
+            match self.remote_refs(config) {
+                Ok(refs) => {
+                    if refs.contains(&tag_ref) {
+                        failed.push(("tag", format!("tag {:?} already exists on the remote", tag_name)));
+                    }
+                }
+                Err(err) => {
+                    response.warning(format!(
+                        "cannot check if tag {:?} exists on the remote: {}",
+                        tag_name, err
+                    ));
+                }
+            }
+        }
+
+        checks_result(failed)
+    }
+
+    /// Changed and untracked files, except for the ones ignored by git and `semantic-rs` own state
+    fn changed_files(&self) -> Result<Vec<String>, git2::Error> {
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false)
+            .exclude_submodules(true);
+
+        let statuses = self.repo.statuses(Some(&mut options))?;
+        let changed = statuses
+            .iter()
+            .filter(|entry| !entry.status().is_ignored())
+            .filter_map(|entry| entry.path().map(str::to_owned))
+            .filter(|path| !path.starts_with(".semantic-rs/"))
+            .collect();
+
+        Ok(changed)
+    }
+
+    /// Names of the refs advertised by the remote
+    fn remote_refs(&self, config: &Config) -> Result<Vec<String>, failure::Error> {
+        let mut remote = self.repo.find_remote(config.remote.as_value())?;
+        let remote_url = remote.url().ok_or(Error::GitRemoteUndefined)?.to_owned();
+        let callbacks = config.auth.as_value().callbacks(&self.repo, &remote_url)?;
+
+        let connection = remote.connect_auth(Direction::Fetch, Some(callbacks), None)?;
+        let refs = connection.list()?.iter().map(|head| head.name().to_owned()).collect();
+
+        Ok(refs)
     }
 
     fn perform_pre_flight_overrides(&mut self, config: &Config) -> Result<(), failure::Error> {
@@ -153,23 +514,10 @@ impl State {
                 .ok_or(Error::GitRemoteUndefined)?;
 
             if !is_https_remote(&remote_url) {
-                // TODO: replace with generic regex
-                let rules = [
-                    ("git@github.com:", "https://github.com/"),
-                    ("git://github.com/", "https://github.com/"),
-                ];
-
-                let mut new_url = None;
-
-                for (pattern, substitute) in &rules {
-                    if remote_url.starts_with(pattern) {
-                        new_url = Some(remote_url.replace(pattern, substitute));
-                        break;
-                    }
-                }
-
-                let url = new_url.ok_or(Error::RemoteNotSupportedForHttpsForcing(remote_url))?;
+                let url = url::to_https(&remote_url, config.url_rewrites.as_value())?
+                    .ok_or(Error::RemoteNotSupportedForHttpsForcing(remote_url))?;
 
+                log::info!("Using {} as the remote url", url);
                 self.set_remote_url(config, &url)?;
             }
         }
@@ -184,8 +532,11 @@ impl State {
     }
 
     fn commit_files(&self, config: &Config, files: &[String], commit_msg: &str) -> Result<(), failure::Error> {
-        let files = files.iter().filter(|filename| {
-            let path = Path::new(filename);
+        let files = files
+            .iter()
+            .map(|filename| self.repo_path(config, filename))
+            .collect::<Result<Vec<_>, _>>()?;
+        let files = files.iter().filter(|path| {
             !self
                 .repo
                 .status_should_ignore(path)
@@ -199,6 +550,20 @@ impl State {
         Ok(())
     }
 
+    /// Path in the repository of a file given relative to the project root
+    fn repo_path(&self, config: &Config, file: &str) -> Result<PathBuf, Error> {
+        let path = Path::new(config.project_root.as_value()).join(file);
+        let not_found = || Error::FileToCommitNotFound(path.display().to_string());
+
+        let workdir = self.repo.workdir().and_then(|dir| dir.canonicalize().ok());
+        let path = path.canonicalize().map_err(|_| not_found())?;
+        let path = workdir
+            .and_then(|workdir| path.strip_prefix(workdir).ok().map(Path::to_owned))
+            .ok_or_else(not_found)?;
+
+        Ok(path)
+    }
+
     fn add<P: AsRef<Path>>(&self, files: impl Iterator<Item = P>) -> Result<(), git2::Error> {
         let mut index = self.repo.index()?;
 
@@ -209,7 +574,7 @@ impl State {
         index.write()
     }
 
-    fn commit(&self, config: &Config, message: &str) -> Result<(), git2::Error> {
+    fn commit(&self, config: &Config, message: &str) -> Result<(), failure::Error> {
         let update_ref = format!("refs/heads/{}", config.branch.as_value());
 
         let oid = self.repo.refname_to_id("HEAD")?;
@@ -220,68 +585,247 @@ impl State {
         let tree_oid = index.write_tree()?;
         let tree = self.repo.find_tree(tree_oid)?;
 
-        self.repo
-            .commit(
-                Some(&update_ref),
-                &self.signature,
-                &self.signature,
-                message,
-                &tree,
-                &parents,
-            )
-            .map(|_| ())
+        if let Some(signer) = &self.signer {
+            // git2 0.9 has no `commit_create_buffer`: write the unsigned commit without updating any reference
+            // and sign its raw content instead, the dangling object is pruned by `git gc`
+            let unsigned = self
+                .repo
+                .commit(None, &self.signature, &self.signature, message, &tree, &parents)?;
+            let content = sign::raw_object(&self.repo, unsigned)?;
+            let signature = signer.sign(&content)?;
+            let commit = self.repo.commit_signed(&content, &signature, None)?;
+            self.repo.reference(&update_ref, commit, true, "commit (signed)")?;
+            return Ok(());
+        }
+
+        self.repo.commit(
+            Some(&update_ref),
+            &self.signature,
+            &self.signature,
+            message,
+            &tree,
+            &parents,
+        )?;
+
+        Ok(())
     }
 
-    fn create_tag(&self, config: &Config, tag_name: &str, message: &str) -> Result<(), git2::Error> {
+    fn create_tag(&self, config: &Config, tag_name: &str, message: &str) -> Result<(), failure::Error> {
         let rev = format!("refs/heads/{}", config.branch.as_value());
         let obj = self.repo.revparse_single(&rev)?;
 
-        self.repo
-            .tag(tag_name, &obj, &self.signature, message, false)
-            .map(|_| ())
+        if let Some(signer) = &self.signer {
+            // The signature is appended to the tag object, so the message must end with a newline
+            let message = if message.ends_with('\n') {
+                message.to_owned()
+            } else {
+                format!("{}\n", message)
+            };
+            // Same as for the commit, the unsigned tag is created by libgit2 and replaced by the signed one
+            let unsigned = self.repo.tag(tag_name, &obj, &self.signature, &message, false)?;
+            let mut content = sign::raw_object(&self.repo, unsigned)?;
+            match signer.sign(&content) {
+                Ok(signature) => content.push_str(&signature),
+                Err(err) => {
+                    self.repo.tag_delete(tag_name)?;
+                    return Err(err.into());
+                }
+            }
+            let tag = self.repo.odb()?.write(git2::ObjectType::Tag, content.as_bytes())?;
+            self.repo
+                .reference(&format!("refs/tags/{}", tag_name), tag, true, "tag (signed)")?;
+            return Ok(());
+        }
+
+        self.repo.tag(tag_name, &obj, &self.signature, message, false)?;
+
+        Ok(())
     }
 
     pub fn push(&self, config: &Config, tag_name: &str) -> Result<(), failure::Error> {
+        // We need to push both the branch we just committed as well as the tag we created.
+        let branch_ref = format!("refs/heads/{}", config.branch.as_value());
+        let tag_ref = format!("refs/tags/{}", tag_name);
+
+        self.push_refs(config, &[&branch_ref[..], &tag_ref[..]])
+    }
+
+    fn push_refs(&self, config: &Config, refs: &[&str]) -> Result<(), failure::Error> {
         let repo = &self.repo;
 
-        let branch = config.branch.as_value();
         let remote = config.remote.as_value();
-        let token = std::env::var("GH_TOKEN").ok();
-
-        // We need to push both the branch we just committed as well as the tag we created.
-        let branch_ref = format!("refs/heads/{}", branch);
-        let tag_ref = format!("refs/tags/{}", tag_name);
-        let refs = [&branch_ref[..], &tag_ref[..]];
 
         let mut remote = repo.find_remote(remote)?;
-        let remote_url = remote.url().ok_or(Error::GitRemoteUndefined)?;
-        let mut cbs = RemoteCallbacks::new();
+        let remote_url = remote.url().ok_or(Error::GitRemoteUndefined)?.to_owned();
         let mut opts = PushOptions::new();
+        opts.remote_callbacks(config.auth.as_value().callbacks(repo, &remote_url)?);
+
+        remote.push(refs, Some(&mut opts))?;
+
+        Ok(())
+    }
+
+    /// Fetch the branch from the remote and return the commit it points to there
+    fn fetch_branch(&self, config: &Config) -> Result<Option<Oid>, failure::Error> {
+        let branch = config.branch.as_value();
+        let tracking_ref = format!("refs/remotes/{}/{}", config.remote.as_value(), branch);
+
+        let mut remote = self.repo.find_remote(config.remote.as_value())?;
+        let remote_url = remote.url().ok_or(Error::GitRemoteUndefined)?.to_owned();
+        let mut opts = FetchOptions::new();
+        opts.remote_callbacks(config.auth.as_value().callbacks(&self.repo, &remote_url)?);
+        // The release tag is being deleted, it mustn't be fetched back
+        opts.download_tags(AutotagOption::None);
+
+        let refspec = format!("+refs/heads/{}:{}", branch, tracking_ref);
+        remote.fetch(&[&refspec[..]], Some(&mut opts), None)?;
 
-        if is_https_remote(remote_url) {
-            let token = token.ok_or(Error::GithubTokenUndefined)?;
-            cbs.credentials(move |_url, _username, _allowed| Cred::userpass_plaintext(&token, ""));
-            opts.remote_callbacks(cbs);
+        Ok(self.repo.refname_to_id(&tracking_ref).ok())
+    }
+
+    /// Commit the files, tag the commit and push both, recording the progress in `release`
+    fn release(
+        &self,
+        config: &Config,
+        files: &[String],
+        commit_msg: &str,
+        tag_name: &str,
+        tag_message: &str,
+        release: &mut Release,
+    ) -> Result<(), failure::Error> {
+        log::info!("Committing files {:?}", files);
+        self.commit_files(config, files, commit_msg)?;
+        log::info!("Creating tag {:?}", tag_name);
+        self.create_tag(config, tag_name, tag_message)?;
+        release.tag_name = Some(tag_name.to_owned());
+        log::info!("Pushing changes, please wait...");
+        self.push(config, tag_name)?;
+        release.pushed = true;
+        Ok(())
+    }
+
+    /// Reset the release commit and delete the release tag, both locally and, if they were pushed, on the remote
+    fn rollback(&self, config: &Config, release: &Release) -> Result<(), failure::Error> {
+        let branch_ref = format!("refs/heads/{}", config.branch.as_value());
+        let release_commit = self.repo.refname_to_id(&branch_ref)?;
+
+        log::info!("Resetting {} to {}", branch_ref, release.previous_head);
+        let is_head = self.repo.head()?.name() == Some(&branch_ref[..]);
+        if is_head {
+            let previous_head = self.repo.find_object(release.previous_head, None)?;
+            self.repo.reset(&previous_head, ResetType::Mixed, None)?;
         } else {
-            cbs.credentials(|_url, username, _allowed| Cred::ssh_key_from_agent(&username.unwrap()));
-            opts.remote_callbacks(cbs);
+            self.repo
+                .reference(&branch_ref, release.previous_head, true, "rollback release commit")?;
         }
 
-        remote.push(&refs, Some(&mut opts))?;
+        let tag_name = match &release.tag_name {
+            Some(tag_name) => tag_name,
+            None => return Ok(()),
+        };
+        log::info!("Deleting tag {:?}", tag_name);
+        self.repo.tag_delete(tag_name)?;
 
-        Ok(())
+        // A failed push may have been rejected because the remote moved on, so it's left untouched
+        if !release.pushed {
+            return Ok(());
+        }
+
+        log::info!("Pushing rollback, please wait...");
+        let delete_tag_ref = format!(":refs/tags/{}", tag_name);
+
+        // Forcing the branch back would wipe anything pushed to it since the release
+        if self.fetch_branch(config)? != Some(release_commit) {
+            log::warn!(
+                "{} was updated on {:?} since the release, only the tag is deleted there",
+                branch_ref,
+                config.remote.as_value()
+            );
+            return self.push_refs(config, &[&delete_tag_ref[..]]);
+        }
+
+        let force_branch_ref = format!("+{}", branch_ref);
+        self.push_refs(config, &[&force_branch_ref[..], &delete_tag_ref[..]])
     }
 
-    fn latest_tag(&self) -> Option<(GitRevision, semver::Version)> {
-        let tags = self.repo.tag_names(None).ok()?;
+    fn head_branch(&self) -> Result<Option<String>, failure::Error> {
+        if self.repo.head_detached()? {
+            return Ok(None);
+        }
 
-        tags.iter()
+        let head = self.repo.head()?;
+        Ok(head.shorthand().map(ToOwned::to_owned))
+    }
+
+    /// Pre-release branch HEAD is on and its pre-release identifier
+    fn prerelease_branch(&self, config: &Config) -> Result<Option<(String, String)>, failure::Error> {
+        let channel = self.head_branch()?.and_then(|branch| {
+            let channel = config.prerelease_branches.as_value().get(&branch)?.clone();
+            Some((branch, channel))
+        });
+
+        Ok(channel)
+    }
+
+    /// Maintenance branch HEAD is on and its version range
+    fn maintenance_branch(&self, config: &Config) -> Result<Option<(String, VersionRange)>, failure::Error> {
+        let range = self.head_branch()?.and_then(|branch| {
+            let range = *config.maintenance_branches.as_value().get(&branch)?;
+            Some((branch, range))
+        });
+
+        Ok(range)
+    }
+
+    /// Latest release on the channel: stable releases are always taken into account,
+    /// pre-releases only if they were made on the same channel.
+    /// Maintenance branches only take into account the releases in their range.
+    fn latest_tag(&self, config: &Config) -> Result<Option<(GitRevision, semver::Version)>, failure::Error> {
+        let pattern = tag_pattern(&config.tag_format(), config.package())?;
+        let tags = self.repo.tag_names(None)?;
+
+        let latest = tags
+            .iter()
             .filter_map(std::convert::identity)
             .filter_map(|tag| {
-                let nums = if tag.starts_with('v') { &tag[1..] } else { &tag[..] };
-                semver::Version::parse(nums).ok().map(|v| (tag.to_owned(), v))
+                let version = pattern.captures(tag)?.name("version")?.as_str();
+                semver::Version::parse(version).ok().map(|v| (tag.to_owned(), v))
             })
-            .max_by(|(_, v1), (_, v2)| v1.cmp(v2))
+            .filter(|(_, version)| is_on_channel(version, self.channel.as_deref()))
+            .filter(|(_, version)| self.range.into_iter().all(|range| range.contains(version)))
+            .max_by(|(_, v1), (_, v2)| v1.cmp(v2));
+
+        Ok(latest)
+    }
+
+    fn template_vars(&self, config: &Config) -> TemplateVars {
+        let previous_version = self
+            .current_version
+            .as_ref()
+            .and_then(|v| v.semver.as_ref())
+            .map(ToString::to_string)
+            .unwrap_or_default();
+
+        TemplateVars {
+            version: config.next_version.as_value().to_string(),
+            package: config.package().to_owned(),
+            previous_version,
+            branch: config.branch.as_value().clone(),
+            date: format_date(self.signature.when()),
+            // Release notes are only provided on Verify Release, while the release tag is rendered before
+            release_notes: if config.changelog.is_ready() {
+                config.changelog.as_value().clone()
+            } else {
+                String::new()
+            },
+        }
+    }
+
+    fn tag_name(&self, config: &Config) -> Result<String, Error> {
+        match self.release.as_ref().and_then(|release| release.tag_name.clone()) {
+            Some(tag_name) => Ok(tag_name),
+            None => render_template(&config.tag_format(), &self.template_vars(config)),
+        }
     }
 
     fn earliest_revision(&self) -> Result<Oid, failure::Error> {
@@ -301,6 +845,55 @@ impl GitPlugin {
             state: None,
         }
     }
+
+    /// Open the repository and apply the branch and remote overrides, checking the repository if `run_checks` is set
+    fn start(&mut self, run_checks: bool) -> Result<PluginResponseBuilder<()>, failure::Error> {
+        let mut response = PluginResponse::builder();
+
+        log::debug!("git(config): {:?}", self.config);
+
+        let mut data = {
+            let path = self.config.project_root.as_value();
+            // The project root of a monorepo package is a subdirectory of the repository
+            let repo = Repository::discover(path)?;
+            State::new(&self.config, repo)?
+        };
+
+        if let Some((branch, channel)) = data.prerelease_branch(&self.config)? {
+            log::info!(
+                "Releasing branch {:?} as a pre-release on channel {:?}",
+                branch,
+                channel
+            );
+            self.config.branch = Value::with_value("branch", branch);
+            data.channel = Some(channel);
+        }
+
+        if let Some((branch, range)) = data.maintenance_branch(&self.config)? {
+            log::info!("Releasing maintenance branch {:?} limited to {}", branch, range);
+            self.config.branch = Value::with_value("branch", branch);
+            data.range = Some(range);
+        }
+
+        let config = &self.config;
+
+        tag_pattern(&config.tag_format(), config.package())?;
+        url::validate_rewrites(config.url_rewrites.as_value())?;
+
+        if let Some(signer) = &data.signer {
+            log::debug!("git(pre_flight): checking the signing key");
+            signer.check()?;
+        }
+
+        if run_checks {
+            data.perform_pre_flight_checks(config, &mut response)?;
+        }
+        data.perform_pre_flight_overrides(config)?;
+
+        self.state = Some(data);
+
+        Ok(response)
+    }
 }
 
 impl PluginInterface for GitPlugin {
@@ -319,11 +912,18 @@ impl PluginInterface for GitPlugin {
             ProvisionCapability::builder(GIT_REMOTE_URL)
                 .after_step(PluginStep::PreFlight)
                 .build(),
+            ProvisionCapability::builder(RELEASE_CHANNEL)
+                .after_step(PluginStep::PreFlight)
+                .build(),
+            ProvisionCapability::builder(RELEASE_RANGE)
+                .after_step(PluginStep::PreFlight)
+                .build(),
             ProvisionCapability::builder(CURRENT_VERSION)
                 .after_step(PluginStep::GetLastRelease)
                 .build(),
-            ProvisionCapability::builder("release_tag")
-                .after_step(PluginStep::Commit)
+            ProvisionCapability::builder(RELEASE_TAG)
+                .after_step(PluginStep::DeriveNextVersion)
+                .derived_from(NEXT_VERSION)
                 .build(),
         ])
     }
@@ -341,6 +941,14 @@ impl PluginInterface for GitPlugin {
                     return PluginResponse::from_error(Error::GitRemoteUndefined.into());
                 }
             }
+            "release_channel" => {
+                let state = self.state.as_ref().ok_or(Error::StateIsNone)?;
+                serde_json::to_value(&state.channel)?
+            }
+            "release_range" => {
+                let state = self.state.as_ref().ok_or(Error::StateIsNone)?;
+                serde_json::to_value(state.range)?
+            }
             "current_version" => serde_json::to_value(
                 self.state
                     .as_ref()
@@ -352,7 +960,20 @@ impl PluginInterface for GitPlugin {
                         )
                     })?,
             )?,
-            "release_tag" => serde_json::to_value(format!("v{}", self.config.next_version.as_value()))?,
+            "release_tag" => {
+                let state = self.state.as_ref().ok_or(Error::StateIsNone)?;
+                // The tag is rendered as soon as the next version is known, before it is created on Commit
+                if !self.config.next_version.is_ready() {
+                    return PluginResponse::from_error(
+                        FlowError::DataNotAvailableYet(
+                            key.to_owned(),
+                            Availability::AfterStep(PluginStep::DeriveNextVersion),
+                        )
+                        .into(),
+                    );
+                }
+                serde_json::to_value(state.tag_name(&self.config)?)?
+            }
             other => return PluginResponse::from_error(FlowError::KeyNotSupported(other.to_owned()).into()),
         };
 
@@ -369,37 +990,32 @@ impl PluginInterface for GitPlugin {
     }
 
     fn methods(&self) -> response::Methods {
-        let methods = vec![PluginStep::PreFlight, PluginStep::GetLastRelease, PluginStep::Commit];
+        let methods = vec![
+            PluginStep::PreFlight,
+            PluginStep::GetLastRelease,
+            PluginStep::VerifyRelease,
+            PluginStep::Commit,
+        ];
         PluginResponse::from_ok(methods)
     }
 
     fn pre_flight(&mut self) -> response::Null {
-        let mut response = PluginResponse::builder();
-
-        let config = &self.config;
-
-        log::debug!("git(config): {:?}", config);
-
-        let mut data = {
-            let path = config.project_root.as_value();
-            let repo = Repository::open(path)?;
-            State::new(config, repo)?
-        };
-
-        data.perform_pre_flight_checks(config, &mut response);
-        data.perform_pre_flight_overrides(config)?;
-
+        let mut response = self.start(true)?;
         log::debug!("git(pre_flight): finished");
+        response.body(())
+    }
 
-        self.state = Some(data);
-
+    /// The release may have left changes in the working tree, so the pre-flight checks are skipped
+    fn resume(&mut self) -> response::Null {
+        let mut response = self.start(false)?;
+        log::debug!("git(resume): finished");
         response.body(())
     }
 
     fn get_last_release(&mut self) -> response::Null {
         let state = self.state.as_mut().ok_or(Error::StateIsNone)?;
 
-        let version = match state.latest_tag() {
+        let version = match state.latest_tag(&self.config)? {
             Some((rev, version)) => Version {
                 rev,
                 semver: Some(version),
@@ -418,23 +1034,47 @@ impl PluginInterface for GitPlugin {
         PluginResponse::from_ok(())
     }
 
+    fn verify_release(&mut self) -> response::Null {
+        let mut response = PluginResponse::builder();
+        let state = self.state.as_ref().ok_or(Error::StateIsNone)?;
+
+        state.perform_tag_check(&self.config, &mut response)?;
+
+        response.body(())
+    }
+
     fn commit(&mut self) -> response::Null {
-        let next_version = self.config.next_version.as_value();
         let files_to_commit = self.config.files_to_commit.as_value();
         let changelog = self.config.changelog.as_value();
-        let state = self.state.as_ref().ok_or(Error::StateIsNone)?;
+        let state = self.state.as_mut().ok_or(Error::StateIsNone)?;
         let config = &self.config;
 
-        // TODO: make releaserc-configurable
-        let commit_msg = format!("chore(release): Version {} [skip ci]", next_version);
-        let tag_name = format!("v{}", next_version);
+        let vars = state.template_vars(config);
+        let commit_msg = render_template(config.commit_message.as_value(), &vars)?;
+        let tag_name = render_template(&config.tag_format(), &vars)?;
 
-        log::info!("Committing files {:?}", files_to_commit);
-        state.commit_files(config, &files_to_commit, &commit_msg)?;
-        log::info!("Creating tag {:?}", tag_name);
-        state.create_tag(config, &tag_name, &changelog)?;
-        log::info!("Pushing changes, please wait...");
-        state.push(config, &tag_name)?;
+        let previous_head = state
+            .repo
+            .refname_to_id(&format!("refs/heads/{}", config.branch.as_value()))?;
+
+        let mut release = Release {
+            previous_head,
+            tag_name: None,
+            pushed: false,
+        };
+        let result = state.release(config, files_to_commit, &commit_msg, &tag_name, changelog, &mut release);
+        state.release = Some(release);
+        result?;
+
+        PluginResponse::from_ok(())
+    }
+
+    fn rollback(&mut self) -> response::Null {
+        if let Some(state) = self.state.as_mut() {
+            if let Some(release) = state.release.take() {
+                state.rollback(&self.config, &release)?;
+            }
+        }
 
         PluginResponse::from_ok(())
     }
@@ -454,15 +1094,556 @@ pub enum Error {
     CommitterEmailUndefined,
     #[fail(display = "failed to determine git remote url")]
     GitRemoteUndefined,
-    #[fail(display = "GH_TOKEN is undefined: cannot push changes")]
-    GithubTokenUndefined,
     #[fail(
-        display = "{} is not supported for https forcing, please consider opening an issue at https://github.com/etclabscore/semantic-rs/issues/new/choose",
+        display = "{} is not supported for https forcing, add a matching rule to cfg.git.url_rewrites",
         _0
     )]
     RemoteNotSupportedForHttpsForcing(String),
+    #[fail(display = "unknown placeholder {{{}}} in template {:?}", _0, _1)]
+    UnknownPlaceholder(String, String),
+    #[fail(display = "unclosed placeholder in template {:?}", _0)]
+    MalformedTemplate(String),
+    #[fail(
+        display = "cfg.git.tag_format {:?} must contain exactly one {{version}} placeholder",
+        _0
+    )]
+    TagFormatWithoutVersion(String),
+    #[fail(
+        display = "cfg.git.tag_format {:?} must not contain the {{release_notes}} placeholder",
+        _0
+    )]
+    TagFormatWithReleaseNotes(String),
+    #[fail(display = "file to commit {} not found in the repository", _0)]
+    FileToCommitNotFound(String),
+    #[fail(display = "repository checks failed:{}", _0)]
+    ChecksFailed(String),
+}
+
+/// Whether the version is a stable one or a pre-release on the channel
+fn is_on_channel(version: &semver::Version, channel: Option<&str>) -> bool {
+    match (version.pre.first(), channel) {
+        (None, _) => true,
+        (Some(semver::Identifier::AlphaNumeric(id)), Some(channel)) => id == channel,
+        _ => false,
+    }
+}
+
+/// Turn the list of failed checks into an error listing every failure with the option to disable the check
+fn checks_result(failed: Vec<(&str, String)>) -> Result<(), failure::Error> {
+    if failed.is_empty() {
+        return Ok(());
+    }
+
+    let failures = failed
+        .into_iter()
+        .map(|(check, failure)| format!("\n\t{} (disable with cfg.git.checks.{} = false)", failure, check))
+        .collect::<String>();
+
+    Err(Error::ChecksFailed(failures).into())
 }
 
-fn is_https_remote(remote: &str) -> bool {
-    remote.starts_with("https://")
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    fn vars() -> TemplateVars {
+        TemplateVars {
+            version: "1.2.3".into(),
+            package: "crate-a".into(),
+            previous_version: "1.2.2".into(),
+            branch: "master".into(),
+            date: "2019-10-01".into(),
+            release_notes: "notes".into(),
+        }
+    }
+
+    #[test]
+    fn render_templates() {
+        assert_eq!(
+            render_template(&default_commit_message(), &vars()).unwrap(),
+            "chore(release): Version 1.2.3 [skip ci]"
+        );
+        assert_eq!(render_template(&default_tag_format(), &vars()).unwrap(), "v1.2.3");
+        assert_eq!(
+            render_template(
+                "{branch}: {previous_version} -> {version} ({date})\n\n{release_notes}",
+                &vars()
+            )
+            .unwrap(),
+            "master: 1.2.2 -> 1.2.3 (2019-10-01)\n\nnotes"
+        );
+    }
+
+    #[test]
+    fn render_template_errors() {
+        assert!(render_template("release-{unknown}", &vars()).is_err());
+        assert!(render_template("release-{version", &vars()).is_err());
+    }
+
+    #[test]
+    fn parse_tags() {
+        let parse = |format: &str, tag: &str| {
+            tag_pattern(format, "crate-a")
+                .unwrap()
+                .captures(tag)
+                .map(|c| c["version"].to_owned())
+        };
+
+        assert_eq!(parse("v{version}", "v1.2.3").as_deref(), Some("1.2.3"));
+        assert_eq!(parse("v{version}", "1.2.3").as_deref(), Some("1.2.3"));
+        assert_eq!(parse("release-{version}", "release-1.2.3").as_deref(), Some("1.2.3"));
+        assert_eq!(parse("release-{version}", "v1.2.3"), None);
+        assert_eq!(parse("{branch}/v{version}", "stable/v1.2.3").as_deref(), Some("1.2.3"));
+
+        assert_eq!(
+            parse("{package}-v{version}", "crate-a-v1.2.3").as_deref(),
+            Some("1.2.3")
+        );
+        assert_eq!(parse("{package}-v{version}", "crate-b-v1.2.3"), None);
+        assert_eq!(parse("{package}-v{version}", "v1.2.3"), None);
+
+        assert!(tag_pattern("release", "").is_err());
+        assert!(tag_pattern("{version}-{version}", "").is_err());
+        assert!(tag_pattern("v{version}-{release_notes}", "").is_err());
+    }
+
+    #[test]
+    fn signed_commit_and_tag() {
+        let dir = TempDir::new("git-signed");
+
+        let key = dir.join("id_ed25519");
+        let keygen = std::process::Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "test", "-f"])
+            .arg(&key)
+            .status()
+            .unwrap();
+        assert!(keygen.success());
+
+        let repo = Repository::init(dir.join("repo")).unwrap();
+        {
+            let signature = Signature::now("John Doe", "jd@example.com").unwrap();
+            let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+            repo.commit(Some("refs/heads/master"), &signature, &signature, "init", &tree, &[])
+                .unwrap();
+            repo.set_head("refs/heads/master").unwrap();
+        }
+
+        let config = Config {
+            user_name: Value::with_value("user_name", Some("John Doe".into())),
+            user_email: Value::with_value("user_email", Some("jd@example.com".into())),
+            sign: Value::with_value("sign", Some(SignFormat::Ssh)),
+            signing_key: Value::with_value("signing_key", Some(key.display().to_string())),
+            ..Config::default()
+        };
+        let state = State::new(&config, repo).unwrap();
+
+        state.commit(&config, "chore(release): Version 1.0.0").unwrap();
+        let head = state.repo.refname_to_id("refs/heads/master").unwrap();
+        let (signature, content) = state.repo.extract_signature(&head, None).unwrap();
+        assert!(signature.as_str().unwrap().starts_with("-----BEGIN SSH SIGNATURE-----"));
+
+        // The signature covers the commit as stored
+        let signature_path = dir.join("commit.sig");
+        std::fs::write(&signature_path, &*signature).unwrap();
+        let verify = subprocess::Exec::cmd("ssh-keygen")
+            .args(&["-Y", "check-novalidate", "-n", "git", "-s"])
+            .arg(&signature_path)
+            .stdin(content.to_vec())
+            .stdout(subprocess::Redirection::Pipe)
+            .stderr(subprocess::Redirection::Merge)
+            .capture()
+            .unwrap();
+        assert!(verify.success(), "{}", verify.stdout_str());
+        let commit = state.repo.find_commit(head).unwrap();
+        assert_eq!(commit.parent_count(), 1);
+        assert_eq!(commit.message(), Some("chore(release): Version 1.0.0"));
+        assert_eq!(commit.committer().name(), Some("John Doe"));
+
+        state.create_tag(&config, "v1.0.0", "notes").unwrap();
+        let tag = state
+            .repo
+            .find_reference("refs/tags/v1.0.0")
+            .unwrap()
+            .peel(git2::ObjectType::Tag)
+            .unwrap()
+            .into_tag()
+            .unwrap();
+        assert_eq!(tag.target_id(), head);
+        assert!(tag
+            .message()
+            .unwrap()
+            .starts_with("notes\n-----BEGIN SSH SIGNATURE-----"));
+        assert_eq!(tag.tagger().unwrap().name(), Some("John Doe"));
+
+        // A tag that couldn't be signed is not left behind unsigned
+        let broken = Config {
+            signing_key: Value::with_value("signing_key", Some(dir.join("missing").display().to_string())),
+            ..config
+        };
+        let state = State::new(&broken, Repository::open(dir.join("repo")).unwrap()).unwrap();
+        assert!(state.create_tag(&broken, "v1.0.1", "notes").is_err());
+        assert!(state.repo.find_reference("refs/tags/v1.0.1").is_err());
+    }
+
+    /// Repository with a single commit on master and a bare `origin` remote
+    fn checks_repo(name: &str) -> (TempDir, State, Config) {
+        let dir = TempDir::new(&format!("git-{}", name));
+
+        let origin = Repository::init_bare(dir.join("origin")).unwrap();
+        let repo = Repository::init(dir.join("repo")).unwrap();
+        let signature = Signature::now("John Doe", "jd@example.com").unwrap();
+        {
+            let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+            let head = repo
+                .commit(Some("refs/heads/master"), &signature, &signature, "init", &tree, &[])
+                .unwrap();
+            repo.set_head("refs/heads/master").unwrap();
+            repo.reference("refs/remotes/origin/master", head, true, "").unwrap();
+
+            let tree = origin
+                .find_tree(origin.treebuilder(None).unwrap().write().unwrap())
+                .unwrap();
+            let origin_head = origin
+                .commit(Some("refs/heads/master"), &signature, &signature, "init", &tree, &[])
+                .unwrap();
+            origin.reference("refs/tags/v2.0.0", origin_head, true, "").unwrap();
+        }
+        repo.remote("origin", origin.path().to_str().unwrap()).unwrap();
+
+        let config = Config {
+            user_name: Value::with_value("user_name", Some("John Doe".into())),
+            user_email: Value::with_value("user_email", Some("jd@example.com".into())),
+            next_version: Value::with_value(NEXT_VERSION, semver::Version::new(1, 0, 0)),
+            changelog: Value::with_value(RELEASE_NOTES, "notes".into()),
+            package: Value::with_value(PACKAGE, None),
+            ..Config::default()
+        };
+        let state = State::new(&config, repo).unwrap();
+
+        (dir, state, config)
+    }
+
+    fn pre_flight_checks(state: &State, config: &Config) -> Result<(), failure::Error> {
+        state.perform_pre_flight_checks(config, &mut PluginResponseBuilder::<()>::new())
+    }
+
+    fn tag_check(state: &State, config: &Config) -> Result<(), failure::Error> {
+        state.perform_tag_check(config, &mut PluginResponseBuilder::<()>::new())
+    }
+
+    fn disable(config: &Config, update: impl FnOnce(&mut Checks)) -> Config {
+        let mut checks = config.checks.as_value().clone();
+        update(&mut checks);
+        Config {
+            checks: Value::with_value("checks", checks),
+            ..config.clone()
+        }
+    }
+
+    #[test]
+    fn checks_pass() {
+        let (_dir, state, config) = checks_repo("checks-pass");
+        pre_flight_checks(&state, &config).unwrap();
+        tag_check(&state, &config).unwrap();
+    }
+
+    #[test]
+    fn check_branch() {
+        let (_dir, state, config) = checks_repo("check-branch");
+        let config = Config {
+            branch: Value::with_value("branch", "release".into()),
+            checks: Value::with_value(
+                "checks",
+                Checks {
+                    up_to_date: false,
+                    ..Checks::default()
+                },
+            ),
+            ..config
+        };
+
+        let err = pre_flight_checks(&state, &config).unwrap_err().to_string();
+        assert!(err.contains("HEAD is on branch \"master\", but the release branch is \"release\""));
+        assert!(err.contains("cfg.git.checks.branch = false"));
+        pre_flight_checks(&state, &disable(&config, |c| c.branch = false)).unwrap();
+
+        let head = state.repo.refname_to_id("HEAD").unwrap();
+        state.repo.set_head_detached(head).unwrap();
+        let err = pre_flight_checks(&state, &disable(&config, |c| c.branch = false)).unwrap_err();
+        assert!(err.to_string().contains("HEAD is detached"));
+        pre_flight_checks(
+            &state,
+            &disable(&config, |c| {
+                c.branch = false;
+                c.detached_head = false
+            }),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn check_clean() {
+        let (_dir, state, config) = checks_repo("check-clean");
+        let workdir = state.repo.workdir().unwrap().to_owned();
+
+        std::fs::create_dir_all(workdir.join(".semantic-rs")).unwrap();
+        std::fs::write(workdir.join(".semantic-rs/state.json"), "{}").unwrap();
+        pre_flight_checks(&state, &config).unwrap();
+
+        std::fs::write(workdir.join("Cargo.toml"), "").unwrap();
+        let err = pre_flight_checks(&state, &config).unwrap_err();
+        assert!(err.to_string().contains("uncommitted changes: Cargo.toml"));
+        pre_flight_checks(&state, &disable(&config, |c| c.clean = false)).unwrap();
+
+        std::fs::write(workdir.join(".gitignore"), "Cargo.toml\n.gitignore\n").unwrap();
+        pre_flight_checks(&state, &config).unwrap();
+    }
+
+    #[test]
+    fn check_up_to_date() {
+        let (_dir, state, config) = checks_repo("check-up-to-date");
+
+        let head = state
+            .repo
+            .find_commit(state.repo.refname_to_id("HEAD").unwrap())
+            .unwrap();
+        let remote_head = state
+            .repo
+            .commit(
+                None,
+                &state.signature,
+                &state.signature,
+                "remote",
+                &head.tree().unwrap(),
+                &[&head],
+            )
+            .unwrap();
+        state
+            .repo
+            .reference("refs/remotes/origin/master", remote_head, true, "")
+            .unwrap();
+
+        let err = pre_flight_checks(&state, &config).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("1 commit(s) behind refs/remotes/origin/master"));
+        pre_flight_checks(&state, &disable(&config, |c| c.up_to_date = false)).unwrap();
+    }
+
+    #[test]
+    fn check_tag() {
+        let (_dir, state, config) = checks_repo("check-tag");
+
+        let head = state.repo.refname_to_id("HEAD").unwrap();
+        state.repo.reference("refs/tags/v1.0.0", head, true, "").unwrap();
+        let err = tag_check(&state, &config).unwrap_err();
+        assert!(err.to_string().contains("tag \"v1.0.0\" already exists"));
+        tag_check(&state, &disable(&config, |c| c.tag = false)).unwrap();
+
+        let config = Config {
+            next_version: Value::with_value(NEXT_VERSION, semver::Version::new(2, 0, 0)),
+            ..config
+        };
+        let err = tag_check(&state, &config).unwrap_err();
+        assert!(err.to_string().contains("tag \"v2.0.0\" already exists on the remote"));
+    }
+
+    #[test]
+    fn push_to_local_remote() {
+        let (dir, state, config) = checks_repo("push");
+        let origin = dir.join("origin");
+        state
+            .repo
+            .remote_set_url("origin", &format!("file://{}", origin.display()))
+            .unwrap();
+        let config = Config {
+            auth: Value::with_value("auth", Auth::None),
+            ..config
+        };
+
+        state.commit(&config, "chore(release): Version 1.0.0").unwrap();
+        state.create_tag(&config, "v1.0.0", "notes").unwrap();
+        state.push(&config, "v1.0.0").unwrap();
+
+        let origin = Repository::open_bare(origin).unwrap();
+        assert_eq!(
+            origin.refname_to_id("refs/heads/master").unwrap(),
+            state.repo.refname_to_id("refs/heads/master").unwrap()
+        );
+        assert!(origin.find_reference("refs/tags/v1.0.0").is_ok());
+    }
+
+    #[test]
+    fn rollback_failed_push() {
+        let (dir, state, config) = checks_repo("rollback");
+        state
+            .repo
+            .remote_set_url("origin", &format!("file://{}", dir.join("missing").display()))
+            .unwrap();
+        let config = Config {
+            auth: Value::with_value("auth", Auth::None),
+            ..config
+        };
+
+        let previous_head = state.repo.refname_to_id("HEAD").unwrap();
+        let mut release = Release {
+            previous_head,
+            tag_name: None,
+            pushed: false,
+        };
+        let result = state.release(
+            &config,
+            &[],
+            "chore(release): Version 1.0.0",
+            "v1.0.0",
+            "notes",
+            &mut release,
+        );
+        assert!(result.is_err());
+        assert_eq!(release.tag_name.as_deref(), Some("v1.0.0"));
+        assert!(!release.pushed);
+
+        state.rollback(&config, &release).unwrap();
+        assert_eq!(state.repo.refname_to_id("HEAD").unwrap(), previous_head);
+        assert!(state.repo.find_reference("refs/tags/v1.0.0").is_err());
+    }
+
+    #[test]
+    fn rollback_pushed_release() {
+        let (dir, state, config) = checks_repo("rollback-pushed");
+        let config = Config {
+            auth: Value::with_value("auth", Auth::None),
+            ..config
+        };
+        let remote = Repository::init_bare(dir.join("remote")).unwrap();
+        state
+            .repo
+            .remote_set_url("origin", remote.path().to_str().unwrap())
+            .unwrap();
+        let previous_head = state.repo.refname_to_id("HEAD").unwrap();
+
+        let release = |moved: bool| {
+            let mut release = Release {
+                previous_head,
+                tag_name: None,
+                pushed: false,
+            };
+            state
+                .release(
+                    &config,
+                    &[],
+                    "chore(release): Version 1.0.0",
+                    "v1.0.0",
+                    "notes",
+                    &mut release,
+                )
+                .unwrap();
+            assert!(release.pushed);
+
+            // Someone else pushes to the branch after the release
+            let release_commit = remote.refname_to_id("refs/heads/master").unwrap();
+            if moved {
+                let parent = remote.find_commit(release_commit).unwrap();
+                let signature = Signature::now("Jane Doe", "jane@example.com").unwrap();
+                remote
+                    .commit(
+                        Some("refs/heads/master"),
+                        &signature,
+                        &signature,
+                        "other",
+                        &parent.tree().unwrap(),
+                        &[&parent],
+                    )
+                    .unwrap();
+            }
+
+            state.rollback(&config, &release).unwrap();
+            assert_eq!(state.repo.refname_to_id("HEAD").unwrap(), previous_head);
+            assert!(remote.find_reference("refs/tags/v1.0.0").is_err());
+            remote.refname_to_id("refs/heads/master").unwrap()
+        };
+
+        assert_eq!(release(false), previous_head);
+        let remote_head = release(true);
+        assert_ne!(remote_head, previous_head);
+        assert_eq!(remote.find_commit(remote_head).unwrap().message(), Some("other"));
+    }
+
+    #[test]
+    fn prerelease_channels() {
+        let (_dir, mut state, config) = checks_repo("channels");
+        let head = state.repo.refname_to_id("HEAD").unwrap();
+        for tag in &["v1.0.0", "v1.1.0-rc.1", "v1.2.0-beta.2", "v0.9.0"] {
+            state
+                .repo
+                .reference(&format!("refs/tags/{}", tag), head, true, "")
+                .unwrap();
+        }
+
+        let latest = |state: &State| state.latest_tag(&config).unwrap().map(|(tag, _)| tag);
+        assert_eq!(latest(&state).as_deref(), Some("v1.0.0"));
+        state.channel = Some("rc".into());
+        assert_eq!(latest(&state).as_deref(), Some("v1.1.0-rc.1"));
+        state.channel = Some("alpha".into());
+        assert_eq!(latest(&state).as_deref(), Some("v1.0.0"));
+
+        let config = Config {
+            prerelease_branches: Value::with_value(
+                "prerelease_branches",
+                vec![("next".to_owned(), "rc".to_owned())].into_iter().collect(),
+            ),
+            ..config
+        };
+        assert_eq!(state.prerelease_branch(&config).unwrap(), None);
+        let commit = state.repo.find_commit(head).unwrap();
+        state.repo.branch("next", &commit, false).unwrap();
+        state.repo.set_head("refs/heads/next").unwrap();
+        assert_eq!(
+            state.prerelease_branch(&config).unwrap(),
+            Some(("next".to_owned(), "rc".to_owned()))
+        );
+    }
+
+    #[test]
+    fn maintenance_branches() {
+        let (_dir, mut state, config) = checks_repo("maintenance");
+        let head = state.repo.refname_to_id("HEAD").unwrap();
+        for tag in &["v1.2.3", "v1.3.1", "v2.0.0", "v1.2.4-rc.1"] {
+            state
+                .repo
+                .reference(&format!("refs/tags/{}", tag), head, true, "")
+                .unwrap();
+        }
+
+        let config = Config {
+            maintenance_branches: Value::with_value(
+                "maintenance_branches",
+                serde_json::from_value(serde_json::json!({ "1.x": "1.x", "1.2.x": "1.2.x" })).unwrap(),
+            ),
+            ..config
+        };
+        assert_eq!(state.maintenance_branch(&config).unwrap(), None);
+
+        let commit = state.repo.find_commit(head).unwrap();
+        state.repo.branch("1.2.x", &commit, false).unwrap();
+        state.repo.set_head("refs/heads/1.2.x").unwrap();
+        let (branch, range) = state.maintenance_branch(&config).unwrap().unwrap();
+        assert_eq!(branch, "1.2.x");
+
+        let latest = |state: &State| state.latest_tag(&config).unwrap().map(|(tag, _)| tag);
+        assert_eq!(latest(&state).as_deref(), Some("v2.0.0"));
+        state.range = Some(range);
+        assert_eq!(latest(&state).as_deref(), Some("v1.2.3"));
+        state.range = config.maintenance_branches.as_value().get("1.x").cloned();
+        assert_eq!(latest(&state).as_deref(), Some("v1.3.1"));
+    }
+
+    #[test]
+    fn format_dates() {
+        assert_eq!(format_date(git2::Time::new(0, 0)), "1970-01-01");
+        assert_eq!(format_date(git2::Time::new(1_569_974_400, 0)), "2019-10-02");
+        assert_eq!(format_date(git2::Time::new(1_569_974_400, -60)), "2019-10-01");
+        assert_eq!(format_date(git2::Time::new(951_782_400, 0)), "2000-02-29");
+    }
 }
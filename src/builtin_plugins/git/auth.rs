@@ -0,0 +1,172 @@
+//! Credentials used to talk to the git remote
+use std::env;
+use std::path::PathBuf;
+
+use failure::Fail;
+use git2::{Cred, RemoteCallbacks, Repository};
+use serde::{Deserialize, Serialize};
+
+const GH_TOKEN: &str = "GH_TOKEN";
+
+/// `cfg.git.auth`, the credential provider is selected by `method`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Auth {
+    /// `GH_TOKEN` for HTTPS remotes and the ssh agent for everything else
+    #[default]
+    Default,
+    /// Token from the environment variable, sent as the password of `username`
+    /// or as the username itself if `username` is not set (that's what GitHub expects)
+    Token {
+        #[serde(default = "default_token_env")]
+        env: String,
+        #[serde(default)]
+        username: Option<String>,
+    },
+    /// Private key file, optionally protected with the passphrase from the environment variable
+    SshKey {
+        private_key: PathBuf,
+        #[serde(default)]
+        public_key: Option<PathBuf>,
+        #[serde(default)]
+        passphrase_env: Option<String>,
+    },
+    SshAgent,
+    /// Credential helpers configured in git config (`credential.helper`)
+    CredentialHelper,
+    /// No credentials, e.g. for local `file://` remotes
+    None,
+}
+
+fn default_token_env() -> String {
+    GH_TOKEN.into()
+}
+
+impl Auth {
+    /// Remote callbacks providing the credentials, environment variables are read eagerly
+    /// so that a missing variable is reported instead of an authentication failure
+    pub fn callbacks(&self, repo: &Repository, remote_url: &str) -> Result<RemoteCallbacks<'static>, failure::Error> {
+        let mut cbs = RemoteCallbacks::new();
+
+        match self {
+            Auth::Default if is_https_remote(remote_url) => {
+                let token = env_var(GH_TOKEN)?;
+                cbs.credentials(move |_url, _username, _allowed| Cred::userpass_plaintext(&token, ""));
+            }
+            Auth::Default | Auth::SshAgent => {
+                cbs.credentials(|_url, username, _allowed| Cred::ssh_key_from_agent(username.unwrap_or("git")));
+            }
+            Auth::Token { env, username } => {
+                let token = env_var(env)?;
+                let username = username.clone();
+                cbs.credentials(move |_url, _username, _allowed| match &username {
+                    Some(username) => Cred::userpass_plaintext(username, &token),
+                    None => Cred::userpass_plaintext(&token, ""),
+                });
+            }
+            Auth::SshKey {
+                private_key,
+                public_key,
+                passphrase_env,
+            } => {
+                if !private_key.exists() {
+                    return Err(Error::PrivateKeyNotFound(private_key.display().to_string()).into());
+                }
+                let passphrase = passphrase_env.as_ref().map(|env| env_var(env)).transpose()?;
+                let private_key = private_key.clone();
+                let public_key = public_key.clone();
+                cbs.credentials(move |_url, username, _allowed| {
+                    Cred::ssh_key(
+                        username.unwrap_or("git"),
+                        public_key.as_deref(),
+                        &private_key,
+                        passphrase.as_deref(),
+                    )
+                });
+            }
+            Auth::CredentialHelper => {
+                let config = repo.config()?;
+                cbs.credentials(move |url, username, _allowed| Cred::credential_helper(&config, url, username));
+            }
+            Auth::None => (),
+        }
+
+        Ok(cbs)
+    }
+}
+
+fn env_var(name: &str) -> Result<String, Error> {
+    env::var(name).map_err(|_| Error::TokenUndefined(name.to_owned()))
+}
+
+pub fn is_https_remote(remote: &str) -> bool {
+    remote.starts_with("https://")
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "{} is undefined: cannot authenticate to the git remote", _0)]
+    TokenUndefined(String),
+    #[fail(display = "ssh private key {} not found", _0)]
+    PrivateKeyNotFound(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    fn auth(value: serde_json::Value) -> Auth {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn deserialize_auth() {
+        assert_eq!(auth(serde_json::json!({ "method": "default" })), Auth::Default);
+        assert_eq!(
+            auth(serde_json::json!({ "method": "token" })),
+            Auth::Token {
+                env: "GH_TOKEN".into(),
+                username: None,
+            }
+        );
+        assert_eq!(
+            auth(serde_json::json!({ "method": "token", "env": "GITLAB_TOKEN", "username": "oauth2" })),
+            Auth::Token {
+                env: "GITLAB_TOKEN".into(),
+                username: Some("oauth2".into()),
+            }
+        );
+        assert_eq!(
+            auth(serde_json::json!({ "method": "ssh_key", "private_key": "deploy_key", "passphrase_env": "KEY_PASS" })),
+            Auth::SshKey {
+                private_key: "deploy_key".into(),
+                public_key: None,
+                passphrase_env: Some("KEY_PASS".into()),
+            }
+        );
+        assert_eq!(auth(serde_json::json!({ "method": "none" })), Auth::None);
+        assert!(serde_json::from_value::<Auth>(serde_json::json!({ "method": "ssh_key" })).is_err());
+    }
+
+    #[test]
+    fn missing_credentials() {
+        let dir = TempDir::new("git-auth");
+        let repo = Repository::init(&dir).unwrap();
+
+        let token = Auth::Token {
+            env: "SEMANTIC_RS_TEST_UNDEFINED_TOKEN".into(),
+            username: None,
+        };
+        assert!(token.callbacks(&repo, "https://example.com/repo.git").is_err());
+
+        let ssh_key = Auth::SshKey {
+            private_key: dir.join("missing_key"),
+            public_key: None,
+            passphrase_env: None,
+        };
+        assert!(ssh_key.callbacks(&repo, "git@example.com:repo.git").is_err());
+
+        assert!(Auth::None.callbacks(&repo, "file:///tmp/repo.git").is_ok());
+    }
+}
@@ -0,0 +1,178 @@
+//! Signing of release commits and tags with GPG or SSH keys
+//!
+//! Signatures are produced the same way `git` does it: by piping the raw object to `gpg` or `ssh-keygen`.
+use failure::Fail;
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use subprocess::{Exec, Redirection};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignFormat {
+    Gpg,
+    Ssh,
+}
+
+impl SignFormat {
+    fn program(self) -> &'static str {
+        match self {
+            SignFormat::Gpg => "gpg",
+            SignFormat::Ssh => "ssh-keygen",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Signer {
+    format: SignFormat,
+    /// GPG key id or path to the SSH private key
+    key: Option<String>,
+    /// Extra environment of the signing program
+    env: Vec<(String, String)>,
+}
+
+impl Signer {
+    pub fn new(format: SignFormat, key: Option<String>) -> Result<Self, Error> {
+        if format == SignFormat::Ssh && key.is_none() {
+            return Err(Error::SigningKeyUndefined);
+        }
+
+        Ok(Signer {
+            format,
+            key,
+            env: Vec::new(),
+        })
+    }
+
+    #[cfg(test)]
+    fn with_env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Sign a throwaway buffer to make sure the key exists and is usable without user interaction
+    pub fn check(&self) -> Result<(), Error> {
+        self.sign("semantic-rs signing key check\n").map(|_| ())
+    }
+
+    /// Detached ASCII-armored signature of the content
+    pub fn sign(&self, content: &str) -> Result<String, Error> {
+        let mut args = Vec::new();
+        match (self.format, &self.key) {
+            (SignFormat::Gpg, key) => {
+                args.extend(&["--status-fd=2", "--batch", "-bsa"]);
+                if let Some(key) = key {
+                    args.extend(&["-u", key.as_str()]);
+                }
+            }
+            (SignFormat::Ssh, key) => {
+                args.extend(&["-Y", "sign", "-n", "git", "-f"]);
+                args.push(key.as_ref().map(String::as_str).unwrap_or_default());
+            }
+        }
+
+        let program = self.format.program();
+        let mut exec = Exec::cmd(program).args(&args);
+        for (key, value) in &self.env {
+            exec = exec.env(key, value);
+        }
+        let output = exec
+            .stdin(content.as_bytes().to_vec())
+            .stdout(Redirection::Pipe)
+            .stderr(Redirection::Pipe)
+            .capture()
+            .map_err(|err| Error::SignerFailed(program, err.to_string()))?;
+
+        let signature = output.stdout_str();
+        if !output.success() || signature.trim().is_empty() {
+            return Err(Error::SignerFailed(program, output.stderr_str().trim().to_owned()));
+        }
+
+        Ok(signature)
+    }
+}
+
+/// Raw content of a commit or tag object, to be signed
+pub fn raw_object(repo: &Repository, oid: Oid) -> Result<String, failure::Error> {
+    let odb = repo.odb()?;
+    let object = odb.read(oid)?;
+    Ok(String::from_utf8(object.data().to_vec())?)
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "cfg.git.signing_key or git config user.signingkey must be set to sign with SSH")]
+    SigningKeyUndefined,
+    #[fail(display = "failed to sign with {}: {}", _0, _1)]
+    SignerFailed(&'static str, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+    use std::process::Command;
+
+    #[test]
+    fn ssh_key_is_required() {
+        assert!(Signer::new(SignFormat::Ssh, None).is_err());
+        assert!(Signer::new(SignFormat::Gpg, None).is_ok());
+    }
+
+    #[test]
+    fn sign_with_ssh() {
+        let dir = TempDir::new("sign-ssh");
+        let key = dir.join("id_ed25519");
+        let status = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "test", "-f"])
+            .arg(&key)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let signer = Signer::new(SignFormat::Ssh, Some(key.display().to_string())).unwrap();
+        signer.check().unwrap();
+        assert!(signer
+            .sign("content")
+            .unwrap()
+            .starts_with("-----BEGIN SSH SIGNATURE-----"));
+
+        let missing = Signer::new(SignFormat::Ssh, Some(dir.join("missing").display().to_string())).unwrap();
+        assert!(missing.check().is_err());
+    }
+
+    #[test]
+    fn sign_with_gpg() {
+        let home = TempDir::new("sign-gpg");
+        let gpg = |args: &[&str]| {
+            Command::new("gpg")
+                .env("GNUPGHOME", &*home)
+                .args(["--batch", "--passphrase", ""])
+                .args(args)
+                .output()
+                .unwrap()
+        };
+        let keygen = gpg(&["--quick-gen-key", "Test <test@example.com>", "ed25519", "sign", "never"]);
+        assert!(keygen.status.success(), "{}", String::from_utf8_lossy(&keygen.stderr));
+
+        let gnupghome = home.display().to_string();
+        let signer = Signer::new(SignFormat::Gpg, Some("test@example.com".into()))
+            .unwrap()
+            .with_env("GNUPGHOME", &gnupghome);
+        signer.check().unwrap();
+        assert!(signer
+            .sign("content")
+            .unwrap()
+            .starts_with("-----BEGIN PGP SIGNATURE-----"));
+
+        let missing = Signer::new(SignFormat::Gpg, Some("missing@example.com".into()))
+            .unwrap()
+            .with_env("GNUPGHOME", &gnupghome);
+        assert!(missing.check().is_err());
+
+        let _ = Command::new("gpgconf")
+            .env("GNUPGHOME", &*home)
+            .args(["--kill", "gpg-agent"])
+            .status();
+    }
+}
@@ -0,0 +1,149 @@
+//! Rewriting of git remote URLs into HTTPS ones for `force_https`
+use failure::Fail;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// `cfg.git.url_rewrites` entry: URLs matching `pattern` are replaced with `replacement`,
+/// which may refer to the capture groups of the pattern as `$1` or `$name`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UrlRewrite {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+impl UrlRewrite {
+    fn apply(&self, url: &str) -> Result<Option<String>, Error> {
+        let regex = Regex::new(&self.pattern).map_err(|err| Error::InvalidPattern(self.pattern.clone(), err))?;
+
+        if regex.is_match(url) {
+            Ok(Some(regex.replace(url, &self.replacement[..]).into_owned()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Check that the patterns of the rules are valid regexes
+pub fn validate_rewrites(rewrites: &[UrlRewrite]) -> Result<(), Error> {
+    for rewrite in rewrites {
+        rewrite.apply("")?;
+    }
+    Ok(())
+}
+
+/// HTTPS URL of the remote: the first matching user rule wins,
+/// otherwise scp-style, `ssh://` and `git://` URLs are rewritten into `https://host/path`
+pub fn to_https(url: &str, rewrites: &[UrlRewrite]) -> Result<Option<String>, Error> {
+    for rewrite in rewrites {
+        if let Some(rewritten) = rewrite.apply(url)? {
+            return Ok(Some(rewritten));
+        }
+    }
+
+    Ok(parse_host_and_path(url).map(|(host, path)| format!("https://{}/{}", host, path)))
+}
+
+fn parse_host_and_path(url: &str) -> Option<(&str, &str)> {
+    let (host, path) = if let Some(scheme_end) = url.find("://") {
+        let scheme = &url[..scheme_end];
+        if !["ssh", "git", "git+ssh", "ssh+git"].contains(&scheme) {
+            return None;
+        }
+
+        let rest = &url[scheme_end + 3..];
+        let path_start = rest.find('/')?;
+        let authority = &rest[..path_start];
+        let host = strip_user(authority);
+        // The port of the ssh or git daemon has nothing to do with the HTTPS one
+        let host = host.split(':').next().unwrap_or(host);
+        (host, &rest[path_start + 1..])
+    } else {
+        // scp-like syntax, `[user@]host:path`, a colon after a slash means a local path
+        let colon = url.find(':')?;
+        if url[..colon].contains('/') {
+            return None;
+        }
+        (strip_user(&url[..colon]), url[colon + 1..].trim_start_matches('/'))
+    };
+
+    if host.is_empty() || path.is_empty() {
+        None
+    } else {
+        Some((host, path))
+    }
+}
+
+fn strip_user(authority: &str) -> &str {
+    authority.rsplit('@').next().unwrap_or(authority)
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "invalid cfg.git.url_rewrites pattern {:?}: {}", _0, _1)]
+    InvalidPattern(String, regex::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn https(url: &str) -> Option<String> {
+        to_https(url, &[]).unwrap()
+    }
+
+    #[test]
+    fn rewrite_generic_urls() {
+        assert_eq!(
+            https("git@github.com:etclabscore/semantic-rs.git").as_deref(),
+            Some("https://github.com/etclabscore/semantic-rs.git")
+        );
+        assert_eq!(
+            https("git://github.com/etclabscore/semantic-rs.git").as_deref(),
+            Some("https://github.com/etclabscore/semantic-rs.git")
+        );
+        assert_eq!(
+            https("ssh://git@gitlab.com:2222/group/subgroup/project.git").as_deref(),
+            Some("https://gitlab.com/group/subgroup/project.git")
+        );
+        assert_eq!(
+            https("git+ssh://gitea.example.com/org/repo").as_deref(),
+            Some("https://gitea.example.com/org/repo")
+        );
+        assert_eq!(
+            https("gitea@gitea.example.com:org/repo.git").as_deref(),
+            Some("https://gitea.example.com/org/repo.git")
+        );
+    }
+
+    #[test]
+    fn unsupported_urls() {
+        assert_eq!(https("file:///srv/git/repo.git"), None);
+        assert_eq!(https("/srv/git/repo.git"), None);
+        assert_eq!(https("./repo:with-colon"), None);
+        assert_eq!(https("git@github.com:"), None);
+    }
+
+    #[test]
+    fn user_rewrites_take_precedence() {
+        let rewrites = vec![UrlRewrite {
+            pattern: "^git@gitea\\.corp:(?P<path>.+)$".into(),
+            replacement: "https://gitea.corp/scm/$path".into(),
+        }];
+
+        assert_eq!(
+            to_https("git@gitea.corp:org/repo.git", &rewrites).unwrap().as_deref(),
+            Some("https://gitea.corp/scm/org/repo.git")
+        );
+        assert_eq!(
+            to_https("git@github.com:org/repo.git", &rewrites).unwrap().as_deref(),
+            Some("https://github.com/org/repo.git")
+        );
+
+        let invalid = vec![UrlRewrite {
+            pattern: "(".into(),
+            replacement: "".into(),
+        }];
+        assert!(validate_rewrites(&invalid).is_err());
+        assert!(validate_rewrites(&rewrites).is_ok());
+    }
+}
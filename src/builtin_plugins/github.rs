@@ -11,7 +11,7 @@ use tokio::runtime::current_thread::block_on_all;
 use url::{ParseError, Url};
 
 use crate::plugin_support::flow::{FlowError, Value};
-use crate::plugin_support::keys::{GIT_BRANCH, GIT_REMOTE, GIT_REMOTE_URL, PROJECT_ROOT};
+use crate::plugin_support::keys::{GIT_BRANCH, GIT_REMOTE, GIT_REMOTE_URL, PROJECT_ROOT, RELEASE_CHANNEL, RELEASE_TAG};
 use crate::plugin_support::proto::response::{self, PluginResponse};
 use crate::plugin_support::{PluginInterface, PluginStep};
 use crate::utils::ResultExt;
@@ -20,12 +20,21 @@ const USERAGENT: &str = concat!("semantic-rs/", env!("CARGO_PKG_VERSION"));
 
 pub struct GithubPlugin {
     config: Config,
+    release: Option<CreatedRelease>,
+}
+
+/// Release created by the publish step, kept to be able to roll it back
+struct CreatedRelease {
+    user: String,
+    repository: String,
+    id: u64,
 }
 
 impl GithubPlugin {
     pub fn new() -> Self {
         GithubPlugin {
             config: Config::default(),
+            release: None,
         }
     }
 }
@@ -42,6 +51,7 @@ pub struct Config {
     changelog: Value<String>,
     draft: Value<bool>,
     pre_release: Value<bool>,
+    release_channel: Value<Option<String>>,
     project_root: Value<String>,
     token: Value<String>,
 }
@@ -55,10 +65,11 @@ impl Default for Config {
             remote: Value::from_key(GIT_REMOTE),
             remote_url: Value::from_key(GIT_REMOTE_URL),
             branch: Value::from_key(GIT_BRANCH),
-            tag_name: Value::required_at("release_tag", PluginStep::Publish),
+            tag_name: Value::required_at(RELEASE_TAG, PluginStep::Publish),
             changelog: Value::required_at("release_notes", PluginStep::Publish),
             draft: Value::with_default_value("draft"),
-            pre_release: Value::with_value("draft", true),
+            pre_release: Value::with_value("pre_release", true),
+            release_channel: Value::required_at(RELEASE_CHANNEL, PluginStep::Publish),
             project_root: Value::protected(PROJECT_ROOT),
             token: Value::load_from_env("GH_TOKEN"),
         }
@@ -163,6 +174,8 @@ impl PluginInterface for GithubPlugin {
         let tag_name = cfg.tag_name.as_value();
         let changelog = cfg.changelog.as_value();
         let token = cfg.token.as_value();
+        // Releases from pre-release branches are always marked as pre-releases
+        let pre_release = *cfg.pre_release.as_value() || cfg.release_channel.as_value().is_some();
 
         // Create release
         let credentials = Credentials::Token(token.to_owned());
@@ -172,7 +185,7 @@ impl PluginInterface for GithubPlugin {
             .body(changelog)
             .commitish(branch)
             .draft(*cfg.draft.as_value())
-            .prerelease(*cfg.pre_release.as_value())
+            .prerelease(pre_release)
             .build();
 
         let release = block_on_all(futures::lazy(move || {
@@ -183,6 +196,12 @@ impl PluginInterface for GithubPlugin {
         }))
         .sync()?;
 
+        self.release = Some(CreatedRelease {
+            user: user.clone(),
+            repository: repo_name.clone(),
+            id: release.id,
+        });
+
         // Upload assets
         let token_header_value = HeaderValue::from_str(&format!("token {}", token)).unwrap();
 
@@ -229,6 +248,28 @@ impl PluginInterface for GithubPlugin {
 
         PluginResponse::from_ok(())
     }
+
+    fn rollback(&mut self) -> response::Null {
+        if let Some(release) = self.release.take() {
+            log::info!(
+                "Deleting release {} from {}/{}",
+                release.id,
+                release.user,
+                release.repository
+            );
+
+            let credentials = Credentials::Token(self.config.token.as_value().to_owned());
+
+            block_on_all(futures::lazy(move || {
+                let github = Github::new(USERAGENT, credentials);
+                let repo = github.repo(release.user, release.repository);
+                repo.releases().delete(release.id)
+            }))
+            .sync()?;
+        }
+
+        PluginResponse::from_ok(())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -326,6 +367,13 @@ pub fn user_repo_from_url(url: &str) -> Result<(String, String), failure::Error>
 mod test {
     use super::*;
 
+    #[test]
+    fn pre_release_by_default() {
+        let config = Config::default();
+        assert!(*config.pre_release.as_value());
+        assert!(!*config.draft.as_value());
+    }
+
     #[test]
     fn parses_remote_urls() {
         let urls = [
@@ -7,7 +7,7 @@ use failure::Fail;
 use serde::{Deserialize, Serialize};
 
 use crate::plugin_support::command::PipedCommand;
-use crate::plugin_support::flow::{FlowError, ProvisionCapability, Value};
+use crate::plugin_support::flow::{FlowError, MergeStrategy, ProvisionCapability, Value};
 use crate::plugin_support::keys::{DRY_RUN, FILES_TO_COMMIT, NEXT_VERSION, PROJECT_ROOT};
 use crate::plugin_support::proto::response::{self, PluginResponse};
 use crate::plugin_support::{PluginInterface, PluginStep};
@@ -43,6 +43,7 @@ impl Default for Config {
             next_version: Value::builder(NEXT_VERSION)
                 .required_at(PluginStep::Prepare)
                 .protected()
+                .merge(MergeStrategy::MaxSemver)
                 .build(),
         }
     }
@@ -82,7 +83,11 @@ impl PluginInterface for RustPlugin {
 
     fn get_value(&self, key: &str) -> response::GetValue {
         let value = match key {
-            "files_to_commit" => serde_json::to_value(vec!["Cargo.toml", "Cargo.lock"])?,
+            "files_to_commit" => {
+                let mut files = vec!["Cargo.toml".to_owned()];
+                files.extend(lock_file(self.config.project_root.as_value()));
+                serde_json::to_value(files)?
+            }
             _other => return PluginResponse::from_error(FlowError::KeyNotSupported(key.to_owned()).into()),
         };
         PluginResponse::from_ok(value)
@@ -162,6 +167,21 @@ impl PluginInterface for RustPlugin {
     }
 }
 
+/// Cargo.lock of the package relative to the project root,
+/// workspace members share the lock file of the workspace root
+fn lock_file(project_root: &str) -> Option<String> {
+    for (depth, dir) in Path::new(project_root).ancestors().enumerate() {
+        if dir.join("Cargo.lock").is_file() {
+            return Some(format!("{}Cargo.lock", "../".repeat(depth)));
+        }
+        // Lock files outside of the repository are none of our business
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
 #[derive(Clone, Debug)]
 struct Cargo {
     manifest_path: PathBuf,
@@ -30,6 +30,12 @@ pub struct Config {
     pub steps: StepsDefinitionMap,
     #[serde(default)]
     pub cfg: ValueDefinitionMap,
+    /// Packages of a monorepo released independently, by package name, with their paths
+    #[serde(default)]
+    pub packages: Map<String, PathBuf>,
+    /// Directory containing `releaserc.toml`, plugin paths are relative to it
+    #[serde(skip)]
+    pub dir: PathBuf,
 }
 
 fn default_project_root() -> ValueDefinition {
@@ -50,8 +56,13 @@ fn default_dry_run() -> ValueDefinition {
     ValueDefinition::Value(serde_json::Value::Bool(false))
 }
 
+fn default_package() -> ValueDefinition {
+    ValueDefinition::Value(serde_json::Value::Null)
+}
+
 impl Config {
     pub fn from_toml<P: AsRef<Path>>(path: P, is_dry_run: bool) -> Result<Self, failure::Error> {
+        let dir = path.as_ref().parent().map(Path::to_path_buf).unwrap_or_default();
         let mut file = File::open(path).map_err(|err| match err.kind() {
             std::io::ErrorKind::NotFound => ConfigError::FileNotFound.into(),
             _other => failure::Error::from(err),
@@ -61,6 +72,7 @@ impl Config {
         file.read_to_string(&mut contents)?;
         let contents = contents.trim();
         let mut config: Config = toml::from_str(contents)?;
+        config.dir = dir;
 
         config.check_step_arguments_correctness()?;
 
@@ -77,6 +89,33 @@ impl Config {
             .entry("project_root".into())
             .or_insert_with(default_project_root);
 
+        config.cfg.entry("package".into()).or_insert_with(default_package);
+
+        Ok(config)
+    }
+
+    /// Configuration of a single package of the monorepo,
+    /// with `project_root` pointing at the package and `package` set to its name
+    pub fn for_package(&self, name: &str) -> Result<Self, failure::Error> {
+        let path = self
+            .packages
+            .get(name)
+            .ok_or_else(|| ConfigError::PackageNotFound(name.to_owned()))?;
+        let root = path
+            .canonicalize()
+            .map_err(|_| ConfigError::PackagePathNotFound(name.to_owned(), path.display().to_string()))?;
+        let root = root
+            .to_str()
+            .map(String::from)
+            .ok_or_else(|| ConfigError::PackagePathNotFound(name.to_owned(), path.display().to_string()))?;
+
+        let mut config = self.clone();
+        config.packages = Map::new();
+        config
+            .cfg
+            .insert("project_root".into(), ValueDefinition::Value(root.into()));
+        config.cfg.insert("package".into(), ValueDefinition::Value(name.into()));
+
         Ok(config)
     }
 
@@ -112,6 +151,10 @@ pub enum ConfigError {
         expected: PluginStepKind,
         got: PluginStepKind,
     },
+    #[fail(display = "package {:?} is not defined in the packages table", _0)]
+    PackageNotFound(String),
+    #[fail(display = "package {:?} not found at {:?}", _0, _1)]
+    PackagePathNotFound(String, String),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -223,6 +266,43 @@ mod tests {
         assert_eq!(&PluginDefinition::Full(UnresolvedPlugin::Builtin), plugin);
     }
 
+    #[test]
+    fn parse_cargo_plugin_full_definition() {
+        let toml = "name = { location = \"cargo\", package = \"my-plugin\", version = \"0.1.0\" }";
+        let parsed: PluginDefinitionMap = toml::from_str(toml).unwrap();
+
+        let plugin = parsed.get("name").expect("plugin 'name' not found in parsed map");
+
+        let expected = UnresolvedPlugin::Cargo {
+            package: "my-plugin".into(),
+            version: "0.1.0".into(),
+        };
+
+        assert_eq!(&PluginDefinition::Full(expected), plugin);
+    }
+
+    #[test]
+    fn parse_path_and_binary_plugin_full_definitions() {
+        let toml = r#"
+            local = { location = "path", path = "./tools/my-plugin" }
+            global = { location = "binary", command = "my-plugin" }
+        "#;
+        let parsed: PluginDefinitionMap = toml::from_str(toml).unwrap();
+
+        let local = parsed.get("local").expect("plugin 'local' not found in parsed map");
+        let global = parsed.get("global").expect("plugin 'global' not found in parsed map");
+
+        let expected_local = UnresolvedPlugin::Path {
+            path: "./tools/my-plugin".into(),
+        };
+        let expected_global = UnresolvedPlugin::Binary {
+            command: "my-plugin".into(),
+        };
+
+        assert_eq!(&PluginDefinition::Full(expected_local), local);
+        assert_eq!(&PluginDefinition::Full(expected_global), global);
+    }
+
     #[test]
     fn parse_builtin_plugin_short_definition() {
         let toml = "name = \"builtin\"";
@@ -571,4 +651,37 @@ mod tests {
 
         drop(parsed)
     }
+
+    #[test]
+    fn package_config() {
+        let toml = r#"
+        [plugins]
+        git = "builtin"
+
+        [steps]
+        commit = "git"
+
+        [packages]
+        semantic-rs = "./"
+        sources = "src"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let keys: Vec<&str> = config.packages.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["semantic-rs", "sources"]);
+
+        let package = config.for_package("sources").unwrap();
+        let root = Path::new("src").canonicalize().unwrap();
+        assert!(package.packages.is_empty());
+        assert_eq!(
+            package.cfg.get("project_root"),
+            Some(&ValueDefinition::Value(root.to_str().unwrap().into()))
+        );
+        assert_eq!(
+            package.cfg.get("package"),
+            Some(&ValueDefinition::Value("sources".into()))
+        );
+
+        assert!(config.for_package("unknown").is_err());
+    }
 }
@@ -1,5 +1,5 @@
-#![feature(try_trait, external_doc)]
-#![doc(include = "../README.md")]
+#![feature(try_trait_v2)]
+#![doc = include_str!("../README.md")]
 
 #[macro_use]
 extern crate strum_macros;
@@ -15,10 +15,13 @@ mod utils;
 
 use crate::builtin_plugins::{early_exit, EarlyExitPlugin};
 use crate::config::Config;
+use crate::plugin_runtime::checkpoint::{Checkpoint, CHECKPOINT_PATH};
+use crate::plugin_runtime::dataflow::{self, DataflowGraph};
 use crate::plugin_runtime::kernel::InjectionTarget;
 use crate::plugin_support::PluginStep;
 use plugin_runtime::Kernel;
 use std::env;
+use std::path::Path;
 
 fn main() {
     if let Err(err) = run() {
@@ -46,6 +49,36 @@ fn run() -> Result<(), failure::Error> {
                 .help("Verbosity level (-v, -vv, -vvv, ...)"),
         )
         .arg(clap::Arg::with_name("silent").long("silent").help("Disable all logs"))
+        .arg(
+            clap::Arg::with_name("resume")
+                .long("resume")
+                .help("Continue the failed release from the checkpoint in .semantic-rs/state.json"),
+        )
+        .arg(
+            clap::Arg::with_name("keep")
+                .long("keep")
+                .help("Keep the steps that succeeded if the release fails, so --resume continues from the failed one"),
+        )
+        .arg(
+            clap::Arg::with_name("plan")
+                .long("plan")
+                .takes_value(true)
+                .min_values(0)
+                .possible_values(&["table", "json"])
+                .help("Print the release plan (as a table by default) and exit without running any step"),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("graph")
+                .about("Print the plugin dataflow graph and exit without running any step")
+                .arg(
+                    clap::Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["dot", "mermaid"])
+                        .default_value("dot")
+                        .help("Output format"),
+                ),
+        )
         .get_matches();
 
     logger::init_logger(clap_args.occurrences_of("verbose"), clap_args.is_present("silent"))?;
@@ -56,13 +89,64 @@ fn run() -> Result<(), failure::Error> {
 
     let config = Config::from_toml("./releaserc.toml", is_dry_run)?;
 
+    let resume = clap_args.is_present("resume");
+
+    if config.packages.is_empty() {
+        return release(config, &clap_args, Path::new(CHECKPOINT_PATH), resume);
+    }
+
+    // Every package of a monorepo is released on its own, with its own checkpoint
+    for name in config.packages.keys() {
+        log::info!("Releasing package {:?}", name);
+        let checkpoint_path = Path::new(CHECKPOINT_PATH)
+            .with_file_name("packages")
+            .join(name)
+            .join("state.json");
+        // Packages released before the failure have no checkpoint left, there's nothing to resume for them
+        let resume = resume && checkpoint_path.exists();
+        release(config.for_package(name)?, &clap_args, &checkpoint_path, resume)?;
+    }
+
+    Ok(())
+}
+
+fn release(
+    config: Config,
+    clap_args: &clap::ArgMatches,
+    checkpoint_path: &Path,
+    resume: bool,
+) -> Result<(), failure::Error> {
+    let is_dry_run = clap_args.is_present("dry");
+
     let kernel = Kernel::builder(config)
         .inject_plugin(
             EarlyExitPlugin::new(),
             InjectionTarget::AfterStep(PluginStep::DeriveNextVersion),
         )
+        .resume(resume)
+        .keep_progress(clap_args.is_present("keep"))
+        .checkpoint_path(checkpoint_path)
         .build()?;
 
+    if let Some(graph_args) = clap_args.subcommand_matches("graph") {
+        let format = graph_args
+            .value_of("format")
+            .unwrap_or("dot")
+            .parse::<dataflow::Format>()?;
+        let graph = DataflowGraph::new(&kernel.plan());
+        print!("{}", graph.render(format));
+        return Ok(());
+    }
+
+    if clap_args.is_present("plan") {
+        let plan = kernel.plan();
+        match clap_args.value_of("plan") {
+            Some("json") => println!("{}", serde_json::to_string_pretty(&plan)?),
+            _ => print!("{}", plan),
+        }
+        return Ok(());
+    }
+
     if let Err(err) = kernel.run() {
         macro_rules! log_error_and_die {
             ($err:expr) => {{
@@ -73,7 +157,12 @@ fn run() -> Result<(), failure::Error> {
 
         match err.downcast::<early_exit::Error>() {
             Ok(ee_error) => match ee_error {
-                early_exit::Error::EarlyExit(_) => (),
+                // Nothing to release, so there's nothing to resume either
+                early_exit::Error::EarlyExit(_) => {
+                    if !is_dry_run {
+                        Checkpoint::remove(checkpoint_path)?;
+                    }
+                }
             },
             Err(other_error) => {
                 log_error_and_die!(other_error);
@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::Path;
+
+use failure::Fail;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Map;
+
+pub const CHECKPOINT_PATH: &str = ".semantic-rs/state.json";
+
+/// Release progress persisted after every action, used to resume a failed release
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// Action sequence the checkpoint was made for, one `<plugin> <action>` entry per action
+    pub sequence: Vec<String>,
+    /// Index of the last completed action
+    pub last_completed_action: Option<usize>,
+    /// Data collected from plugins so far
+    pub globals: Map<String, Vec<serde_json::Value>>,
+    /// `get_config` snapshot of every plugin, by plugin name
+    pub configs: Map<String, serde_json::Value>,
+}
+
+impl Checkpoint {
+    pub fn load(path: &Path) -> Result<Self, failure::Error> {
+        if !path.exists() {
+            return Err(Error::CheckpointNotFound(path.display().to_string()).into());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        let checkpoint = serde_json::from_str(&contents)
+            .map_err(|err| Error::CheckpointMalformed(path.display().to_string(), err.to_string()))?;
+        Ok(checkpoint)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), failure::Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn remove(path: &Path) -> Result<(), failure::Error> {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Index of the first action that has to be run to continue the release
+    pub fn next_action(&self) -> usize {
+        self.last_completed_action.map(|idx| idx + 1).unwrap_or(0)
+    }
+
+    /// Make sure the checkpoint was made for the same sequence, e.g. releaserc.toml wasn't edited in between
+    pub fn check_sequence(&self, sequence: &[String]) -> Result<(), Error> {
+        if self.sequence.len() != sequence.len() {
+            return Err(Error::SequenceMismatch(self.sequence.len(), sequence.len()));
+        }
+
+        match self
+            .sequence
+            .iter()
+            .zip(sequence)
+            .position(|(saved, current)| saved != current)
+        {
+            Some(idx) => Err(Error::SequenceChanged(
+                idx,
+                self.sequence[idx].clone(),
+                sequence[idx].clone(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "nothing to resume: checkpoint {} not found", _0)]
+    CheckpointNotFound(String),
+    #[fail(display = "failed to parse checkpoint {}: {}", _0, _1)]
+    CheckpointMalformed(String, String),
+    #[fail(
+        display = "checkpoint was made for a sequence of {} actions, but the current one has {}: was releaserc.toml changed?",
+        _0, _1
+    )]
+    SequenceMismatch(usize, usize),
+    #[fail(
+        display = "checkpoint was made for a different sequence, action {} was {:?} and is now {:?}: was releaserc.toml changed?",
+        _0, _1, _2
+    )]
+    SequenceChanged(usize, String, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    fn checkpoint() -> Checkpoint {
+        Checkpoint {
+            sequence: vec!["git Call(PreFlight)".to_owned(), "github Call(Publish)".to_owned()],
+            last_completed_action: Some(4),
+            globals: vec![("next_version".to_owned(), vec![serde_json::json!("1.0.0")])]
+                .into_iter()
+                .collect(),
+            configs: vec![("git".to_owned(), serde_json::json!({ "branch": "master" }))]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn next_action() {
+        assert_eq!(checkpoint().next_action(), 5);
+
+        let fresh = Checkpoint {
+            last_completed_action: None,
+            ..checkpoint()
+        };
+        assert_eq!(fresh.next_action(), 0);
+    }
+
+    #[test]
+    fn check_sequence() {
+        let checkpoint = checkpoint();
+        assert!(checkpoint.check_sequence(&checkpoint.sequence).is_ok());
+
+        let reordered = vec!["github Call(Publish)".to_owned(), "git Call(PreFlight)".to_owned()];
+        match checkpoint.check_sequence(&reordered) {
+            Err(Error::SequenceChanged(0, saved, current)) => {
+                assert_eq!(saved, "git Call(PreFlight)");
+                assert_eq!(current, "github Call(Publish)");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(matches!(
+            checkpoint.check_sequence(&reordered[..1]),
+            Err(Error::SequenceMismatch(2, 1))
+        ));
+    }
+
+    #[test]
+    fn save_load_remove() {
+        let dir = TempDir::new("checkpoint");
+        let path = dir.join("state.json");
+
+        checkpoint().save(&path).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap(), checkpoint());
+
+        Checkpoint::remove(&path).unwrap();
+        assert!(Checkpoint::load(&path).is_err());
+    }
+}
@@ -1,10 +1,14 @@
 use failure::Fail;
 
 use crate::config::{Config, Map};
-use crate::plugin_support::flow::Value;
+use crate::plugin_support::flow::{MergeStrategy, Value};
 
 pub struct DataManager {
     global: Map<String, Vec<serde_json::Value>>,
+    /// Plugins that provided each value, by key
+    providers: Map<String, Vec<(String, serde_json::Value)>>,
+    /// Values chosen by `MergeStrategy::MaxSemver` that were already reported, by key
+    decisions: Map<String, serde_json::Value>,
 }
 
 impl DataManager {
@@ -16,53 +20,57 @@ impl DataManager {
                 .filter(|(_, v)| v.is_value())
                 .map(|(k, v)| (k.to_owned(), vec![v.as_value().clone()]))
                 .collect(),
+            providers: Map::new(),
+            decisions: Map::new(),
         }
     }
 
-    pub fn insert_global(&mut self, key: String, value: Value<serde_json::Value>) {
+    pub fn insert_global(&mut self, provider: &str, key: String, value: Value<serde_json::Value>) {
         if value.is_ready() {
-            let vec = self.global.entry(key).or_insert_with(Vec::new);
-
             let value = value.as_value();
+
+            let providers = self.providers.entry(key.clone()).or_default();
+            let provided = (provider.to_owned(), value.clone());
+            if !providers.contains(&provided) {
+                providers.push(provided);
+            }
+
+            let vec = self.global.entry(key).or_insert_with(Vec::new);
             if !vec.contains(value) {
                 vec.push(value.clone());
             }
         }
     }
 
-    // TODO: merging techniques agnostic of destination data type
+    pub fn globals(&self) -> &Map<String, Vec<serde_json::Value>> {
+        &self.global
+    }
+
+    pub fn has_global(&self, key: &str) -> bool {
+        self.global.get(key).into_iter().any(|values| !values.is_empty())
+    }
+
+    /// Replace the collected data, e.g. with the one restored from a checkpoint
+    pub fn restore_globals(&mut self, global: Map<String, Vec<serde_json::Value>>) {
+        self.global = global;
+    }
+
     pub fn prepare_value(
-        &self,
+        &mut self,
         _dst_id: usize,
         _dst_key: &str,
         src_key: &str,
+        merge: MergeStrategy,
     ) -> Result<Value<serde_json::Value>, failure::Error> {
         let values = self
             .global
             .get(src_key)
             .ok_or_else(|| Error::DataNotAvailable(src_key.to_owned()))?;
 
-        let value = match &values[..] {
-            [] => None,
-            [single] => Some(single.clone()),
-            multiple => {
-                // TODO: we need way better type introspection and merging strategies
-                //       then that
-                let mut values = Vec::new();
-                for value in multiple {
-                    if let Some(array) = value.as_array() {
-                        for value in array {
-                            values.push(value);
-                        }
-                    } else {
-                        values.push(value)
-                    }
-                }
-                Some(serde_json::to_value(values)?)
+        if let Some(value) = merge_values(src_key, values, merge)? {
+            if merge == MergeStrategy::MaxSemver && values.len() > 1 {
+                self.report_decision(src_key, &value);
             }
-        };
-
-        if let Some(value) = value {
             Ok(Value::builder(&src_key).value(value).build())
         } else {
             Err(Error::DataNotAvailable(src_key.to_owned()).into())
@@ -70,11 +78,124 @@ impl DataManager {
     }
 
     pub fn prepare_value_same_key(
-        &self,
+        &mut self,
         dst_id: usize,
         dst_key: &str,
     ) -> Result<Value<serde_json::Value>, failure::Error> {
-        self.prepare_value(dst_id, dst_key, dst_key)
+        self.prepare_value(dst_id, dst_key, dst_key, MergeStrategy::default())
+    }
+
+    /// Log which plugins decided the value chosen out of several candidates, once per value
+    fn report_decision(&mut self, key: &str, value: &serde_json::Value) {
+        if self.decisions.get(key) == Some(value) {
+            return;
+        }
+
+        let providers = self.providers.get(key).map(Vec::as_slice).unwrap_or_default();
+        let decided_by = providers
+            .iter()
+            .filter(|(_, provided)| provided == value)
+            .map(|(provider, _)| provider.as_str())
+            .collect::<Vec<_>>();
+        let candidates = providers
+            .iter()
+            .map(|(provider, provided)| format!("{} = {}", provider, provided))
+            .collect::<Vec<_>>();
+
+        if decided_by.is_empty() {
+            log::info!("{} is {} (candidates: {})", key, value, candidates.join(", "));
+        } else {
+            log::info!(
+                "{} is {}, decided by {} (candidates: {})",
+                key,
+                value,
+                decided_by.join(", "),
+                candidates.join(", ")
+            );
+        }
+
+        self.decisions.insert(key.to_owned(), value.clone());
+    }
+}
+
+/// Combine the values provided for the key according to the merge strategy
+fn merge_values(
+    key: &str,
+    values: &[serde_json::Value],
+    merge: MergeStrategy,
+) -> Result<Option<serde_json::Value>, failure::Error> {
+    use serde_json::Value as Json;
+
+    let value = match (merge, values) {
+        (_, []) => None,
+        (MergeStrategy::Concat, [single]) => Some(single.clone()),
+        (MergeStrategy::Concat, multiple) => Some(Json::Array(flatten(multiple))),
+        (MergeStrategy::ConcatUnique, [single]) if !single.is_array() => Some(single.clone()),
+        (MergeStrategy::ConcatUnique, values) => {
+            let mut unique = Vec::new();
+            for value in flatten(values) {
+                if !unique.contains(&value) {
+                    unique.push(value);
+                }
+            }
+            Some(Json::Array(unique))
+        }
+        (MergeStrategy::FirstWins, values) => values.first().cloned(),
+        (MergeStrategy::LastWins, values) => values.last().cloned(),
+        (MergeStrategy::RequireEqual, [first, rest @ ..]) => {
+            if rest.iter().any(|value| value != first) {
+                return Err(Error::ConflictingValues(key.to_owned(), values.to_vec()).into());
+            }
+            Some(first.clone())
+        }
+        (MergeStrategy::DeepMergeObject, values) => {
+            let mut merged = Json::Object(serde_json::Map::new());
+            for value in values {
+                if !value.is_object() {
+                    return Err(Error::MergeTypeMismatch(key.to_owned(), merge.as_str(), value.clone()).into());
+                }
+                deep_merge(&mut merged, value);
+            }
+            Some(merged)
+        }
+        (MergeStrategy::MaxSemver, values) => {
+            let mut max: Option<(semver::Version, &Json)> = None;
+            for value in values {
+                let version = value
+                    .as_str()
+                    .and_then(|v| semver::Version::parse(v).ok())
+                    .ok_or_else(|| Error::MergeTypeMismatch(key.to_owned(), merge.as_str(), value.clone()))?;
+                if !max.as_ref().into_iter().any(|(max, _)| version <= *max) {
+                    max = Some((version, value));
+                }
+            }
+            max.map(|(_, value)| value.clone())
+        }
+    };
+
+    Ok(value)
+}
+
+fn flatten(values: &[serde_json::Value]) -> Vec<serde_json::Value> {
+    let mut flat = Vec::new();
+    for value in values {
+        if let Some(array) = value.as_array() {
+            flat.extend(array.iter().cloned());
+        } else {
+            flat.push(value.clone())
+        }
+    }
+    flat
+}
+
+fn deep_merge(dst: &mut serde_json::Value, src: &serde_json::Value) {
+    match (dst, src) {
+        (serde_json::Value::Object(dst), serde_json::Value::Object(src)) => {
+            for (key, value) in src {
+                deep_merge(dst.entry(key.clone()).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (dst, src) => *dst = src.clone(),
     }
 }
 
@@ -82,4 +203,98 @@ impl DataManager {
 pub enum Error {
     #[fail(display = "no data available for key {}", _0)]
     DataNotAvailable(String),
+    #[fail(display = "key {} was provided with conflicting values: {:?}", _0, _1)]
+    ConflictingValues(String, Vec<serde_json::Value>),
+    #[fail(display = "cannot merge key {} with strategy {}: unexpected value {}", _0, _1, _2)]
+    MergeTypeMismatch(String, &'static str, serde_json::Value),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn merge(values: &[serde_json::Value], merge: MergeStrategy) -> Option<serde_json::Value> {
+        merge_values("key", values, merge).unwrap()
+    }
+
+    #[test]
+    fn merge_concat() {
+        assert_eq!(merge(&[json!("a")], MergeStrategy::Concat), Some(json!("a")));
+        assert_eq!(
+            merge(&[json!(["a", "b"]), json!("b")], MergeStrategy::Concat),
+            Some(json!(["a", "b", "b"]))
+        );
+    }
+
+    #[test]
+    fn merge_concat_unique() {
+        assert_eq!(
+            merge(&[json!(["a", "b"]), json!(["b", "c"])], MergeStrategy::ConcatUnique),
+            Some(json!(["a", "b", "c"]))
+        );
+        assert_eq!(
+            merge(&[json!(["a", "a"])], MergeStrategy::ConcatUnique),
+            Some(json!(["a"]))
+        );
+    }
+
+    #[test]
+    fn merge_first_last_wins() {
+        let values = [json!(1), json!(2), json!(3)];
+        assert_eq!(merge(&values, MergeStrategy::FirstWins), Some(json!(1)));
+        assert_eq!(merge(&values, MergeStrategy::LastWins), Some(json!(3)));
+    }
+
+    #[test]
+    fn merge_require_equal() {
+        assert_eq!(merge(&[json!("a")], MergeStrategy::RequireEqual), Some(json!("a")));
+        assert!(merge_values("key", &[json!("a"), json!("b")], MergeStrategy::RequireEqual).is_err());
+    }
+
+    #[test]
+    fn merge_deep_merge_object() {
+        let values = [
+            json!({ "a": 1, "nested": { "x": 1, "y": 1 } }),
+            json!({ "b": 2, "nested": { "y": 2 } }),
+        ];
+        assert_eq!(
+            merge(&values, MergeStrategy::DeepMergeObject),
+            Some(json!({ "a": 1, "b": 2, "nested": { "x": 1, "y": 2 } }))
+        );
+        assert!(merge_values("key", &[json!([1])], MergeStrategy::DeepMergeObject).is_err());
+    }
+
+    #[test]
+    fn merge_max_semver() {
+        let values = [json!("1.2.0"), json!("2.0.0-rc.1"), json!("1.10.0")];
+        assert_eq!(merge(&values, MergeStrategy::MaxSemver), Some(json!("2.0.0-rc.1")));
+        assert!(merge_values("key", &[json!("not a version")], MergeStrategy::MaxSemver).is_err());
+    }
+
+    #[test]
+    fn most_major_next_version_wins() {
+        let mut data_mgr = DataManager {
+            global: Map::new(),
+            providers: Map::new(),
+            decisions: Map::new(),
+        };
+        let version = |v: &str| Value::builder("next_version").value(json!(v)).build();
+        data_mgr.insert_global("clog", "next_version".into(), version("1.3.0"));
+        data_mgr.insert_global("paths", "next_version".into(), version("2.0.0"));
+        data_mgr.insert_global("paths", "next_version".into(), version("2.0.0"));
+
+        assert_eq!(data_mgr.providers["next_version"].len(), 2);
+
+        let value = data_mgr
+            .prepare_value(0, "next_version", "next_version", MergeStrategy::MaxSemver)
+            .unwrap();
+        assert_eq!(value.as_value(), &json!("2.0.0"));
+        assert_eq!(data_mgr.decisions["next_version"], json!("2.0.0"));
+    }
+
+    #[test]
+    fn merge_nothing() {
+        assert_eq!(merge(&[], MergeStrategy::RequireEqual), None);
+    }
 }
@@ -0,0 +1,353 @@
+//! Plugin dataflow graph rendered as Graphviz DOT or Mermaid
+//!
+//! Every plugin called on a step is a node grouped under that step,
+//! and every key passed between plugins is an edge from the provider to the consumer labelled by the key.
+//! Edges start at the step after which the provider makes the key available.
+use std::fmt::Write as _;
+
+use crate::plugin_runtime::plan::Plan;
+use crate::plugin_support::flow::Availability;
+use crate::plugin_support::PluginStep;
+
+#[derive(EnumString, Debug, Copy, Clone, PartialEq, Eq)]
+#[strum(serialize_all = "snake_case")]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node {
+    /// Step the plugin is called on, `None` for providers of keys that don't depend on any step
+    step: Option<PluginStep>,
+    plugin: String,
+}
+
+impl Node {
+    fn id(&self) -> String {
+        match self.step {
+            Some(step) => format!("{}__{}", step.as_str(), self.plugin),
+            None => self.plugin.clone(),
+        }
+    }
+
+    /// Mermaid node ids are bare words, other characters are replaced with their code
+    fn mermaid_id(&self) -> String {
+        self.id()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c.to_string()
+                } else {
+                    format!("_{:x}", c as u32)
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Edge {
+    from: Node,
+    to: Node,
+    label: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DataflowGraph {
+    clusters: Vec<(PluginStep, Vec<Node>)>,
+    loose: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+impl DataflowGraph {
+    pub fn new(plan: &Plan) -> Self {
+        let mut graph = DataflowGraph::default();
+
+        for step_plan in &plan.steps {
+            let nodes: Vec<Node> = step_plan
+                .plugins
+                .iter()
+                .map(|plugin| Node {
+                    step: Some(step_plan.step),
+                    plugin: plugin.clone(),
+                })
+                .collect();
+
+            for flow in &step_plan.data_flow {
+                // Keys passed to plugins that are not called on this step do not affect the release
+                if !step_plan.plugins.contains(&flow.to) {
+                    continue;
+                }
+
+                let to = Node {
+                    step: Some(step_plan.step),
+                    plugin: flow.to.clone(),
+                };
+                let label = if flow.source_key == flow.dest_key {
+                    flow.source_key.clone()
+                } else {
+                    format!("{} -> {}", flow.source_key, flow.dest_key)
+                };
+
+                for provider in &flow.from {
+                    // The provider is attached to the step the key becomes available after, if it's called on it
+                    let step = match plan.availability(provider, &flow.source_key) {
+                        Some(Availability::AfterStep(step)) => Some(step),
+                        Some(Availability::Always) | None => None,
+                    };
+                    let step = step
+                        .filter(|&step| (plan.steps.iter()).any(|s| s.step == step && s.plugins.contains(provider)));
+                    let from = Node {
+                        step,
+                        plugin: provider.clone(),
+                    };
+
+                    if from.step.is_none() && !graph.loose.contains(&from) {
+                        graph.loose.push(from.clone());
+                    }
+
+                    let edge = Edge {
+                        from,
+                        to: to.clone(),
+                        label: label.clone(),
+                    };
+                    if !graph.edges.contains(&edge) {
+                        graph.edges.push(edge);
+                    }
+                }
+            }
+
+            if !nodes.is_empty() {
+                graph.clusters.push((step_plan.step, nodes));
+            }
+        }
+
+        graph
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Dot => self.to_dot(),
+            Format::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph semantic_rs {{").unwrap();
+        writeln!(out, "    rankdir=LR;").unwrap();
+
+        for (step, nodes) in &self.clusters {
+            writeln!(out, "    subgraph \"cluster_{}\" {{", step.as_str()).unwrap();
+            writeln!(out, "        label=\"{}\";", step.as_str()).unwrap();
+            for node in nodes {
+                writeln!(out, "        {};", dot_node(node)).unwrap();
+            }
+            writeln!(out, "    }}").unwrap();
+        }
+
+        for node in &self.loose {
+            writeln!(out, "    {};", dot_node(node)).unwrap();
+        }
+
+        for edge in &self.edges {
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                dot_escape(&edge.from.id()),
+                dot_escape(&edge.to.id()),
+                dot_escape(&edge.label)
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    fn to_mermaid(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "flowchart LR").unwrap();
+
+        for (step, nodes) in &self.clusters {
+            writeln!(out, "    subgraph {}", step.as_str()).unwrap();
+            for node in nodes {
+                writeln!(out, "        {}", mermaid_node(node)).unwrap();
+            }
+            writeln!(out, "    end").unwrap();
+        }
+
+        for node in &self.loose {
+            writeln!(out, "    {}", mermaid_node(node)).unwrap();
+        }
+
+        for edge in &self.edges {
+            writeln!(
+                out,
+                "    {} -->|\"{}\"| {}",
+                edge.from.mermaid_id(),
+                mermaid_escape(&edge.label),
+                edge.to.mermaid_id()
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
+
+fn dot_node(node: &Node) -> String {
+    format!(
+        "\"{}\" [label=\"{}\"]",
+        dot_escape(&node.id()),
+        dot_escape(&node.plugin)
+    )
+}
+
+/// Escape a DOT quoted string
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_node(node: &Node) -> String {
+    format!("{}[\"{}\"]", node.mermaid_id(), mermaid_escape(&node.plugin))
+}
+
+/// Escape a Mermaid quoted label, quotes can only be written as entity codes there
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_runtime::plan::{KeyFlow, Provision, StepPlan};
+    use crate::plugin_support::flow::MergeStrategy;
+
+    fn flow(from: &[&str], key: &str, to: &str) -> KeyFlow {
+        KeyFlow {
+            from: from.iter().map(|s| s.to_string()).collect(),
+            source_key: key.into(),
+            to: to.into(),
+            dest_key: key.into(),
+            merge: MergeStrategy::Concat,
+        }
+    }
+
+    fn provision(plugin: &str, key: &str, when: Availability) -> Provision {
+        Provision {
+            plugin: plugin.into(),
+            key: key.into(),
+            when,
+        }
+    }
+
+    fn plan() -> Plan {
+        Plan {
+            steps: vec![
+                StepPlan {
+                    step: PluginStep::PreFlight,
+                    plugins: vec!["git".into(), "clog".into()],
+                    data_flow: vec![flow(&["git"], "branch", "clog")],
+                },
+                StepPlan {
+                    step: PluginStep::GetLastRelease,
+                    plugins: vec!["git".into()],
+                    data_flow: vec![],
+                },
+                StepPlan {
+                    step: PluginStep::DeriveNextVersion,
+                    plugins: vec!["clog".into()],
+                    data_flow: vec![
+                        flow(&["git"], "branch", "clog"),
+                        flow(&["git"], "current_version", "clog"),
+                        flow(&["env"], "token", "clog"),
+                        flow(&["clog"], "next_version", "git"),
+                    ],
+                },
+            ],
+            provisions: vec![
+                provision("git", "branch", Availability::AfterStep(PluginStep::PreFlight)),
+                provision(
+                    "git",
+                    "current_version",
+                    Availability::AfterStep(PluginStep::GetLastRelease),
+                ),
+                provision("env", "token", Availability::Always),
+            ],
+            ..Plan::default()
+        }
+    }
+
+    #[test]
+    fn build_graph() {
+        let graph = DataflowGraph::new(&plan());
+
+        assert_eq!(graph.clusters.len(), 3);
+        assert_eq!(graph.loose.iter().map(Node::id).collect::<Vec<_>>(), vec!["env"]);
+
+        let edges = graph
+            .edges
+            .iter()
+            .map(|e| (e.from.id(), e.to.id(), e.label.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                ("pre_flight__git".into(), "pre_flight__clog".into(), "branch"),
+                // Available since pre-flight, even though git is called again before the consumer
+                ("pre_flight__git".into(), "derive_next_version__clog".into(), "branch"),
+                (
+                    "get_last_release__git".into(),
+                    "derive_next_version__clog".into(),
+                    "current_version"
+                ),
+                ("env".into(), "derive_next_version__clog".into(), "token"),
+            ]
+        );
+    }
+
+    #[test]
+    fn render_dot() {
+        let dot = DataflowGraph::new(&plan()).render(Format::Dot);
+        assert!(dot.starts_with("digraph semantic_rs {\n"));
+        assert!(dot.contains("    subgraph \"cluster_pre_flight\" {\n        label=\"pre_flight\";\n"));
+        assert!(dot.contains("    \"pre_flight__git\" -> \"pre_flight__clog\" [label=\"branch\"];\n"));
+    }
+
+    #[test]
+    fn render_mermaid() {
+        let mermaid = DataflowGraph::new(&plan()).render(Format::Mermaid);
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid
+            .contains("    subgraph derive_next_version\n        derive_next_version__clog[\"clog\"]\n    end\n"));
+        assert!(mermaid.contains("    env -->|\"token\"| derive_next_version__clog\n"));
+    }
+
+    #[test]
+    fn escape_labels() {
+        let plan = Plan {
+            steps: vec![StepPlan {
+                step: PluginStep::PreFlight,
+                plugins: vec!["my \"plugin\" [1]".into(), "clog".into()],
+                data_flow: vec![flow(&["my \"plugin\" [1]"], "key \"a\"", "clog")],
+            }],
+            provisions: vec![provision(
+                "my \"plugin\" [1]",
+                "key \"a\"",
+                Availability::AfterStep(PluginStep::PreFlight),
+            )],
+            ..Plan::default()
+        };
+        let graph = DataflowGraph::new(&plan);
+
+        let dot = graph.render(Format::Dot);
+        assert!(dot.contains("\"pre_flight__my \\\"plugin\\\" [1]\" [label=\"my \\\"plugin\\\" [1]\"];"));
+        assert!(dot.contains("-> \"pre_flight__clog\" [label=\"key \\\"a\\\"\"];"));
+
+        let mermaid = graph.render(Format::Mermaid);
+        assert!(mermaid.contains("pre_flight__my_20_22plugin_22_20_5b1_5d[\"my #quot;plugin#quot; [1]\"]"));
+        assert!(mermaid.contains("pre_flight__my_20_22plugin_22_20_5b1_5d -->|\"key #quot;a#quot;\"| pre_flight__clog"));
+    }
+}
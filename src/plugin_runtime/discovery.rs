@@ -1,6 +1,7 @@
-use crate::plugin_support::{Plugin, PluginStep};
+use crate::plugin_support::proto::openrpc::OpenRpcDocument;
+use crate::plugin_support::Plugin;
 
-pub fn discover(plugin: &Plugin) -> Result<Vec<PluginStep>, failure::Error> {
-    let response = plugin.as_interface().methods()?;
+pub fn discover(plugin: &Plugin) -> Result<OpenRpcDocument, failure::Error> {
+    let response = plugin.as_interface().discover()?;
     Ok(response)
 }
@@ -2,7 +2,8 @@ use crate::config::{Config, Map, StepDefinition};
 use crate::plugin_runtime::discovery::discover;
 use crate::plugin_runtime::kernel::{InjectionTarget, PluginId};
 use crate::plugin_support::flow::kv::{Key, ValueDefinition, ValueDefinitionMap, ValueState};
-use crate::plugin_support::flow::{Availability, ProvisionCapability, Value};
+use crate::plugin_support::flow::{Availability, MergeStrategy, ProvisionCapability, Value};
+use crate::plugin_support::proto::openrpc::{ConfigKey, OpenRpcDocument};
 use crate::plugin_support::{Plugin, PluginStep};
 use failure::Fail;
 use std::collections::VecDeque;
@@ -29,8 +30,18 @@ impl Action {
         Action::new(id, ActionKind::Get(src_key.into()))
     }
 
+    #[cfg(test)]
     pub fn set(id: PluginId, dst_key: impl Into<String>, src_key: impl Into<String>) -> Self {
-        Action::new(id, ActionKind::Set(dst_key.into(), src_key.into()))
+        Action::set_merged(id, dst_key, src_key, MergeStrategy::default())
+    }
+
+    pub fn set_merged(
+        id: PluginId,
+        dst_key: impl Into<String>,
+        src_key: impl Into<String>,
+        merge: MergeStrategy,
+    ) -> Self {
+        Action::new(id, ActionKind::Set(dst_key.into(), src_key.into(), merge))
     }
 
     pub fn set_value(id: PluginId, dst_key: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
@@ -49,6 +60,10 @@ impl Action {
         self.id
     }
 
+    pub fn kind(&self) -> &ActionKind {
+        &self.kind
+    }
+
     pub fn into_kind(self) -> ActionKind {
         self.kind
     }
@@ -58,7 +73,7 @@ impl Action {
 pub enum ActionKind {
     Call(PluginStep),
     Get(SourceKey),
-    Set(DestKey, SourceKey),
+    Set(DestKey, SourceKey, MergeStrategy),
     SetValue(DestKey, serde_json::Value),
     RequireConfigEntry(DestKey),
     RequireEnvValue(DestKey, SourceKey),
@@ -67,6 +82,10 @@ pub enum ActionKind {
 #[derive(Debug)]
 pub struct PluginSequence {
     seq: Vec<Action>,
+    /// Steps in the order of execution along with the number of actions generated for each of them
+    steps: Vec<(PluginStep, usize)>,
+    /// Provision capabilities of every plugin, by plugin id
+    caps: Vec<Vec<ProvisionCapability>>,
 }
 
 impl PluginSequence {
@@ -77,20 +96,23 @@ impl PluginSequence {
         is_dry_run: bool,
     ) -> Result<Self, failure::Error> {
         // First -- collect data from plugins
+        let docs = collect_plugins_descriptions(plugins)?;
         let names = collect_plugins_names(plugins);
         let configs = collect_plugins_initial_configuration(plugins)?;
-        let caps = collect_plugins_provision_capabilities(plugins)?;
+        let config_keys = collect_plugins_config_keys(&docs);
+        let caps = collect_plugins_provision_capabilities(&docs);
         let step_map = build_steps_to_plugins_map(
             releaserc,
             plugins,
             injections,
-            collect_plugins_methods_capabilities(plugins)?,
+            collect_plugins_methods_capabilities(plugins, &docs),
         )?;
 
         // Then delegate that data to a builder
         let builder = PluginSequenceBuilder {
             names,
             configs,
+            config_keys,
             caps,
             releaserc: &releaserc.cfg,
             step_map,
@@ -99,19 +121,34 @@ impl PluginSequence {
         builder.build(is_dry_run)
     }
 
-    #[allow(dead_code)]
     pub fn iter(&self) -> impl Iterator<Item = &Action> {
         self.seq.iter()
     }
 
+    #[allow(dead_code)]
     pub fn into_iter(self) -> impl Iterator<Item = Action> {
         self.seq.into_iter()
     }
+
+    pub fn capabilities(&self) -> &[Vec<ProvisionCapability>] {
+        &self.caps
+    }
+
+    /// Iterate over the sequence split by steps
+    pub fn steps(&self) -> impl Iterator<Item = (PluginStep, &[Action])> {
+        let mut rest = &self.seq[..];
+        self.steps.iter().map(move |&(step, len)| {
+            let (actions, tail) = rest.split_at(len);
+            rest = tail;
+            (step, actions)
+        })
+    }
 }
 
 struct PluginSequenceBuilder<'a> {
     names: Vec<String>,
     configs: Vec<Map<String, Value<serde_json::Value>>>,
+    config_keys: Vec<Vec<ConfigKey>>,
     caps: Vec<Vec<ProvisionCapability>>,
     releaserc: &'a ValueDefinitionMap,
     step_map: Map<PluginStep, Vec<PluginId>>,
@@ -123,23 +160,58 @@ impl<'a> PluginSequenceBuilder<'a> {
         self.apply_releaserc_overrides();
 
         let mut seq = Vec::new();
+        let mut steps = Vec::new();
 
         // Generate action sequence for dry steps
         for step in PluginStep::dry_steps() {
             let builder = StepSequenceBuilder::new(step, &self.names, &self.configs, &self.caps, &self.step_map);
-            let step_seq = builder.build();
+            let step_seq = self.skip_undefined_optional(builder.build());
+            steps.push((step, step_seq.len()));
             seq.extend(step_seq.into_iter());
         }
 
         if !is_dry_run {
             for step in PluginStep::wet_steps() {
                 let builder = StepSequenceBuilder::new(step, &self.names, &self.configs, &self.caps, &self.step_map);
-                let step_seq = builder.build();
+                let step_seq = self.skip_undefined_optional(builder.build());
+                steps.push((step, step_seq.len()));
                 seq.extend(step_seq.into_iter());
             }
         }
 
-        Ok(PluginSequence { seq })
+        Ok(PluginSequence {
+            seq,
+            steps,
+            caps: self.caps,
+        })
+    }
+
+    /// Drop the config entries required for optional values that releaserc.toml doesn't define,
+    /// these values are left unprovisioned
+    fn skip_undefined_optional(&self, seq: Vec<Action>) -> Vec<Action> {
+        seq.into_iter()
+            .filter(|action| {
+                let key = match action.kind() {
+                    ActionKind::RequireConfigEntry(key) => key,
+                    _ => return true,
+                };
+
+                let is_optional = self.configs[action.id()].values().any(|value| match &value.state {
+                    ValueState::NeedsProvision(pr) => pr.optional && &pr.key == key,
+                    ValueState::Ready(_) => false,
+                });
+                let is_defined = self.releaserc.get(key).is_some_and(ValueDefinition::is_value);
+
+                if is_optional && !is_defined {
+                    log::debug!(
+                        "optional key {:?} of plugin {:?} is not provided",
+                        key,
+                        self.names[action.id()]
+                    );
+                }
+                !is_optional || is_defined
+            })
+            .collect()
     }
 
     fn apply_releaserc_overrides(&mut self) {
@@ -167,8 +239,9 @@ impl<'a> PluginSequenceBuilder<'a> {
             };
 
             let cfg = &mut self.configs[id];
+            let config_keys = &self.config_keys[id];
             for (dest_key, value_def) in subtable.iter() {
-                if !cfg.contains_key(dest_key) {
+                if !config_keys.iter().any(|k| &k.name == dest_key) {
                     log::warn!(
                         "Key cfg.{}.{} was defined in releaserc.toml but is not supported by plugin {:?}",
                         name,
@@ -187,6 +260,7 @@ impl<'a> PluginSequenceBuilder<'a> {
                         required_at,
                         from_env,
                         key,
+                        merge,
                     } => {
                         let mut new = Value::builder(&key);
                         if let Some(step) = required_at {
@@ -195,7 +269,8 @@ impl<'a> PluginSequenceBuilder<'a> {
                         if *from_env {
                             new.load_from_env();
                         }
-                        cfg.insert(key.clone(), new.build());
+                        new.merge(*merge);
+                        cfg.insert(dest_key.clone(), new.build());
                     }
                 }
             }
@@ -206,11 +281,13 @@ impl<'a> PluginSequenceBuilder<'a> {
 struct StepSequenceBuilder<'a> {
     step: PluginStep,
     names: &'a [String],
+    configs: &'a [Map<String, Value<serde_json::Value>>],
     caps: &'a [Vec<ProvisionCapability>],
     step_map: &'a Map<PluginStep, Vec<PluginId>>,
 
     seq: VecDeque<Action>,
     unresolved: Vec<Vec<(DestKey, SourceKey)>>,
+    derived: Vec<Vec<(DestKey, SourceKey)>>,
     available_always: Map<SourceKey, Vec<PluginId>>,
     available_since: Map<SourceKey, Vec<(PluginId, PluginStep)>>,
     available_same_step: Map<SourceKey, Vec<PluginId>>,
@@ -231,7 +308,7 @@ impl<'a> StepSequenceBuilder<'a> {
         // Here are 2 keys for every plugin:
         // - destination: the key in the plugin config
         // - source: the key advertised by the plugin
-        let unresolved = configs
+        let mut unresolved: Vec<Vec<(DestKey, SourceKey)>> = configs
             .iter()
             .enumerate()
             .map(|(dest_id, config)| {
@@ -259,9 +336,12 @@ impl<'a> StepSequenceBuilder<'a> {
             })
             .collect();
 
+        // Values derived from the config of the providing plugin need that config to be provisioned first,
+        // even if the providing plugin itself only requires it at a later step
+        let derived = Self::split_derived(configs, caps, &mut unresolved);
+
         // TODO:
         // - error-handling for steps skipped in releaserc.toml (if plugin can provide data after step that's skipped -- that should be handled correctly)
-        // - skip generating Call actions for steps that plugins do not implement
         // - rewrite tests
 
         // Collect a few maps from keys to plugins to make life easier
@@ -299,10 +379,12 @@ impl<'a> StepSequenceBuilder<'a> {
         StepSequenceBuilder {
             step,
             names,
+            configs,
             caps,
             step_map,
             seq,
             unresolved,
+            derived,
             available_always,
             available_since,
             available_same_step,
@@ -313,10 +395,18 @@ impl<'a> StepSequenceBuilder<'a> {
     fn build(mut self) -> Vec<Action> {
         let mut seq = std::mem::replace(&mut self.seq, VecDeque::new());
 
-        let unresolved = self.borrow_unresolved();
-
-        // First -- resolve data that's trivially available from the previous step
-        let unresolved = self.resolve_already_available(&mut seq, unresolved);
+        // First -- resolve data that's trivially available from the previous step,
+        // starting with the keys that derived values are computed from
+        let derived = self.resolve_already_available(&mut seq, borrow_keys(&self.derived));
+        let unresolved = self.resolve_already_available(&mut seq, self.borrow_unresolved());
+        let unresolved = derived
+            .into_iter()
+            .zip(unresolved)
+            .map(|(mut keys, rest)| {
+                keys.extend(rest);
+                keys
+            })
+            .collect();
 
         // What's left unresolved is either
         // - inner-step dependencies, where one plugin in the current step depends on data provided by another after running the same step
@@ -364,7 +454,8 @@ impl<'a> StepSequenceBuilder<'a> {
 
                         if let Some(plugins) = self.available_since.get(source_key) {
                             for (src_id, step) in plugins {
-                                if self.is_enabled_for_step(*src_id, *step) {
+                                // Derived values are computed from the provisioned keys, not by running the step
+                                if self.is_enabled_for_step(*src_id, *step) || self.is_derived(*src_id, source_key) {
                                     seq.push_back(Action::get(*src_id, source_key));
                                     resolved = true;
                                 } else {
@@ -377,10 +468,11 @@ impl<'a> StepSequenceBuilder<'a> {
                         }
 
                         if resolved {
-                            seq.push_back(Action::set(
+                            seq.push_back(Action::set_merged(
                                 dest_id,
                                 dest_key,
                                 source_key,
+                                self.merge_strategy(dest_id, dest_key),
                             ));
                             None
                         } else {
@@ -468,7 +560,12 @@ impl<'a> StepSequenceBuilder<'a> {
                             .filter(|&&source_id| source_id != dest_id)
                             .map(|source_id| Action::get(*source_id, *source_key)),
                     );
-                    seq.push_back(Action::set(dest_id, *dest_key, *source_key));
+                    seq.push_back(Action::set_merged(
+                        dest_id,
+                        *dest_key,
+                        *source_key,
+                        self.merge_strategy(dest_id, dest_key),
+                    ));
                 } else {
                     let dest_plugin_name = &self.names[dest_id];
                     log::error!("Plugin {:?} requested key {:?}", dest_plugin_name, source_key);
@@ -505,14 +602,74 @@ impl<'a> StepSequenceBuilder<'a> {
         self.is_enabled_for_step(plugin_id, self.step)
     }
 
-    fn borrow_unresolved(&self) -> Vec<Vec<(&DestKey, &SourceKey)>> {
-        self.unresolved
+    fn is_derived(&self, plugin_id: PluginId, key: &str) -> bool {
+        self.caps[plugin_id]
             .iter()
-            .map(|list| list.iter().map(|(key, value)| (key, value)).collect())
-            .collect()
+            .any(|cap| cap.key == key && !cap.derived_from.is_empty())
+    }
+
+    fn merge_strategy(&self, plugin_id: PluginId, dest_key: &str) -> MergeStrategy {
+        match self.configs[plugin_id].get(dest_key).map(|value| &value.state) {
+            Some(ValueState::NeedsProvision(pr)) => pr.merge,
+            _ => MergeStrategy::default(),
+        }
+    }
+
+    fn borrow_unresolved(&self) -> Vec<Vec<(&DestKey, &SourceKey)>> {
+        borrow_keys(&self.unresolved)
+    }
+
+    // Move the keys that derived values requested in this step are computed from out of `unresolved`,
+    // adding the ones the providing plugin doesn't require yet
+    fn split_derived(
+        configs: &[Map<String, Value<serde_json::Value>>],
+        caps: &[Vec<ProvisionCapability>],
+        unresolved: &mut [Vec<(DestKey, SourceKey)>],
+    ) -> Vec<Vec<(DestKey, SourceKey)>> {
+        let mut derived = vec![Vec::new(); configs.len()];
+
+        for (source_id, caps) in caps.iter().enumerate() {
+            for cap in caps.iter().filter(|cap| !cap.derived_from.is_empty()) {
+                let is_requested = unresolved
+                    .iter()
+                    .enumerate()
+                    .any(|(dest_id, keys)| dest_id != source_id && keys.iter().any(|(_, key)| key == &cap.key));
+                if !is_requested {
+                    continue;
+                }
+
+                for (dest_key, value) in &configs[source_id] {
+                    if let ValueState::NeedsProvision(pr) = &value.state {
+                        let entry = (dest_key.clone(), pr.key.clone());
+                        if !pr.from_env && cap.derived_from.contains(&pr.key) && !derived[source_id].contains(&entry) {
+                            unresolved[source_id].retain(|other| other != &entry);
+                            derived[source_id].push(entry);
+                        }
+                    }
+                }
+            }
+        }
+
+        derived
     }
 }
 
+fn borrow_keys(keys: &[Vec<(DestKey, SourceKey)>]) -> Vec<Vec<(&DestKey, &SourceKey)>> {
+    keys.iter()
+        .map(|list| list.iter().map(|(key, value)| (key, value)).collect())
+        .collect()
+}
+
+fn collect_plugins_descriptions(plugins: &[Plugin]) -> Result<Vec<OpenRpcDocument>, failure::Error> {
+    let mut docs = Vec::new();
+
+    for plugin in plugins.iter() {
+        docs.push(discover(plugin)?);
+    }
+
+    Ok(docs)
+}
+
 fn collect_plugins_names(plugins: &[Plugin]) -> Vec<String> {
     plugins.iter().map(|p| p.name.clone()).collect()
 }
@@ -531,24 +688,19 @@ pub fn collect_plugins_initial_configuration(
     Ok(configs)
 }
 
-fn collect_plugins_provision_capabilities(plugins: &[Plugin]) -> Result<Vec<Vec<ProvisionCapability>>, failure::Error> {
-    let mut caps = Vec::new();
-
-    for plugin in plugins.iter() {
-        let plugin_caps = plugin.as_interface().provision_capabilities()?;
-
-        caps.push(plugin_caps);
-    }
+fn collect_plugins_config_keys(docs: &[OpenRpcDocument]) -> Vec<Vec<ConfigKey>> {
+    docs.iter().map(OpenRpcDocument::config_keys).collect()
+}
 
-    Ok(caps)
+fn collect_plugins_provision_capabilities(docs: &[OpenRpcDocument]) -> Vec<Vec<ProvisionCapability>> {
+    docs.iter().map(OpenRpcDocument::provision_capabilities).collect()
 }
 
-fn collect_plugins_methods_capabilities(plugins: &[Plugin]) -> Result<Map<PluginStep, Vec<String>>, failure::Error> {
+fn collect_plugins_methods_capabilities(plugins: &[Plugin], docs: &[OpenRpcDocument]) -> Map<PluginStep, Vec<String>> {
     let mut capabilities = Map::new();
 
-    for plugin in plugins {
-        let plugin_caps = discover(&plugin)?;
-        for step in plugin_caps {
+    for (plugin, doc) in plugins.iter().zip(docs) {
+        for step in doc.steps() {
             capabilities
                 .entry(step)
                 .or_insert_with(Vec::new)
@@ -556,7 +708,7 @@ fn collect_plugins_methods_capabilities(plugins: &[Plugin]) -> Result<Map<Plugin
         }
     }
 
-    Ok(capabilities)
+    capabilities
 }
 
 fn build_steps_to_plugins_map(
@@ -574,6 +726,8 @@ fn build_steps_to_plugins_map(
             .collect::<Vec<_>>()
     }
 
+    let implements = |step: &PluginStep, name: &str| capabilities.get(step).into_iter().flatten().any(|n| n == name);
+
     for (step, step_def) in config.steps.iter() {
         match step_def {
             StepDefinition::Discover => {
@@ -601,9 +755,7 @@ fn build_steps_to_plugins_map(
                 map.insert(*step, ids);
             }
             StepDefinition::Singleton(plugin) => {
-                let names = capabilities.get(&step).ok_or(Error::NoPluginsForStep(*step))?;
-
-                if !names.contains(&plugin) {
+                if !implements(step, plugin) {
                     return Err(Error::PluginDoesNotImplementStep(*step, plugin.to_string()).into());
                 }
 
@@ -617,10 +769,8 @@ fn build_steps_to_plugins_map(
                     continue;
                 };
 
-                let names = capabilities.get(&step).ok_or(Error::NoPluginsForStep(*step))?;
-
                 for plugin in list {
-                    if !names.contains(&plugin) {
+                    if !implements(step, plugin) {
                         return Err(Error::PluginDoesNotImplementStep(*step, plugin.to_string()).into());
                     }
                 }
@@ -635,6 +785,19 @@ fn build_steps_to_plugins_map(
 
     // Apply injections
     for (id, target) in injections {
+        let step = match target {
+            InjectionTarget::BeforeStep(step) | InjectionTarget::AfterStep(step) => step,
+        };
+
+        if !implements(&step, &plugins[id].name) {
+            log::warn!(
+                "Plugin {:?} was injected into step '{}', but it does not implement this step: skipping",
+                plugins[id].name,
+                step.as_str()
+            );
+            continue;
+        }
+
         match target {
             InjectionTarget::BeforeStep(step) => map.entry(step).or_insert_with(Vec::new).insert(0, id),
             InjectionTarget::AfterStep(step) => map.entry(step).or_insert_with(Vec::new).push(id),
@@ -647,9 +810,7 @@ fn build_steps_to_plugins_map(
 #[derive(Fail, Debug)]
 #[rustfmt::skip]
 enum Error {
-    #[fail(display = "no plugins is capable of satisfying a non-null step {:?}", _0)]
-    NoPluginsForStep(PluginStep),
-    #[fail(display = "step {:?} requested plugin {:?}, but it does not implement this step", _0, 1)]
+    #[fail(display = "step {:?} requested plugin {:?}, but it does not implement this step", _0, _1)]
     PluginDoesNotImplementStep(PluginStep, String),
 }
 
@@ -697,7 +858,9 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: None,
                 from_env: false,
-                key: "source_key".to_string()
+                key: "source_key".to_string(),
+                merge: MergeStrategy::Concat,
+                optional: false,
             })
         );
 
@@ -708,7 +871,8 @@ mod tests {
     #[test]
     fn collect_caps() {
         let plugins = dependent_provider_plugins();
-        let caps = collect_plugins_provision_capabilities(&plugins).unwrap();
+        let docs = collect_plugins_descriptions(&plugins).unwrap();
+        let caps = collect_plugins_provision_capabilities(&docs);
         assert_eq!(
             caps,
             vec![vec![], vec![ProvisionCapability::builder("source_key").build()]]
@@ -730,7 +894,8 @@ mod tests {
 
         let config = toml::from_str(toml).unwrap();
         let plugins = dependent_provider_plugins();
-        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+        let docs = collect_plugins_descriptions(&plugins).unwrap();
+        let caps = collect_plugins_methods_capabilities(&plugins, &docs);
 
         let map = build_steps_to_plugins_map(&config, &plugins, vec![], caps).unwrap();
 
@@ -739,6 +904,60 @@ mod tests {
         assert_eq!(map, expected);
     }
 
+    #[test]
+    fn steps_to_plugins_map_step_not_implemented() {
+        let toml = r#"
+            [plugins]
+            dependent = "builtin"
+            provider = "builtin"
+
+            [steps]
+            pre_flight = [ "dependent", "provider" ]
+            commit = "provider"
+        "#;
+
+        let config = toml::from_str(toml).unwrap();
+        let plugins = dependent_provider_plugins();
+        let caps = vec![
+            (PluginStep::PreFlight, vec!["dependent".into(), "provider".into()]),
+            (PluginStep::Commit, vec!["dependent".into()]),
+        ]
+        .into_iter()
+        .collect();
+
+        let err = build_steps_to_plugins_map(&config, &plugins, vec![], caps).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "step Commit requested plugin \"provider\", but it does not implement this step"
+        );
+    }
+
+    #[test]
+    fn steps_to_plugins_map_injection_not_implemented() {
+        let toml = r#"
+            [plugins]
+            dependent = "builtin"
+            provider = "builtin"
+
+            [steps]
+            pre_flight = "discover"
+        "#;
+
+        let config = toml::from_str(toml).unwrap();
+        let plugins = dependent_provider_plugins();
+        let caps = vec![(PluginStep::PreFlight, vec!["dependent".into()])]
+            .into_iter()
+            .collect();
+        let injections = vec![(1, InjectionTarget::AfterStep(PluginStep::PreFlight))];
+
+        let map = build_steps_to_plugins_map(&config, &plugins, injections, caps).unwrap();
+
+        let expected = vec![(PluginStep::PreFlight, vec![0])].into_iter().collect();
+
+        assert_eq!(map, expected);
+    }
+
     #[test]
     fn steps_to_plugins_map_different_step_order() {
         env_logger::try_init().ok();
@@ -754,7 +973,8 @@ mod tests {
 
         let config = toml::from_str(toml).unwrap();
         let plugins = dependent_provider_plugins();
-        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+        let docs = collect_plugins_descriptions(&plugins).unwrap();
+        let caps = collect_plugins_methods_capabilities(&plugins, &docs);
 
         let map = build_steps_to_plugins_map(&config, &plugins, vec![], caps).unwrap();
 
@@ -778,7 +998,8 @@ mod tests {
 
         let config = toml::from_str(toml).unwrap();
         let plugins = dependent_provider_plugins();
-        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+        let docs = collect_plugins_descriptions(&plugins).unwrap();
+        let caps = collect_plugins_methods_capabilities(&plugins, &docs);
 
         let map = build_steps_to_plugins_map(&config, &plugins, vec![], caps).unwrap();
 
@@ -804,7 +1025,8 @@ mod tests {
         let mut plugins = dependent_provider_plugins();
         plugins.push(Plugin::new(Box::new(test_plugins::Injected)).unwrap());
 
-        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+        let docs = collect_plugins_descriptions(&plugins).unwrap();
+        let caps = collect_plugins_methods_capabilities(&plugins, &docs);
         let injections = vec![(2, InjectionTarget::BeforeStep(PluginStep::PreFlight))];
 
         let map = build_steps_to_plugins_map(&config, &plugins, injections, caps).unwrap();
@@ -831,7 +1053,8 @@ mod tests {
         let mut plugins = dependent_provider_plugins();
         plugins.push(Plugin::new(Box::new(test_plugins::Injected)).unwrap());
 
-        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+        let docs = collect_plugins_descriptions(&plugins).unwrap();
+        let caps = collect_plugins_methods_capabilities(&plugins, &docs);
         let injections = vec![(2, InjectionTarget::BeforeStep(PluginStep::PreFlight))];
 
         let map = build_steps_to_plugins_map(&config, &plugins, injections, caps).unwrap();
@@ -858,7 +1081,8 @@ mod tests {
         let mut plugins = dependent_provider_plugins();
         plugins.push(Plugin::new(Box::new(test_plugins::Injected)).unwrap());
 
-        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+        let docs = collect_plugins_descriptions(&plugins).unwrap();
+        let caps = collect_plugins_methods_capabilities(&plugins, &docs);
         let injections = vec![(2, InjectionTarget::BeforeStep(PluginStep::DeriveNextVersion))];
 
         let map = build_steps_to_plugins_map(&config, &plugins, injections, caps).unwrap();
@@ -889,7 +1113,7 @@ mod tests {
         "#;
 
         let config = toml::from_str(toml).unwrap();
-        let PluginSequence { seq } =
+        let PluginSequence { seq, .. } =
             PluginSequence::new(&dependent_provider_plugins(), &config, vec![], false).unwrap();
 
         let correct_seq: Vec<Action> = PluginStep::iter()
@@ -927,7 +1151,7 @@ mod tests {
         "#;
 
         let config = toml::from_str(toml).unwrap();
-        let PluginSequence { seq } =
+        let PluginSequence { seq, .. } =
             PluginSequence::new(&dependent_provider_plugins(), &config, vec![], false).unwrap();
 
         let correct_seq: Vec<Action> = PluginStep::iter()
@@ -944,6 +1168,132 @@ mod tests {
         assert_eq!(seq, correct_seq);
     }
 
+    #[test]
+    fn merge_strategy_override_of_protected_key() {
+        use crate::builtin_plugins::{ClogPlugin, GitPlugin};
+
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+            clog = "builtin"
+
+            [steps]
+            pre_flight = [ "git", "clog" ]
+            get_last_release = "git"
+            derive_next_version = [ "clog" ]
+            generate_notes = "clog"
+            prepare = [ "clog" ]
+            verify_release = [ "git" ]
+            commit = "git"
+
+            [cfg.git]
+            next_version = "from:next_version:merge=require_equal"
+        "#;
+
+        let config = toml::from_str(toml).unwrap();
+        let plugins = vec![
+            Plugin::new(Box::new(GitPlugin::new())).unwrap(),
+            Plugin::new(Box::new(ClogPlugin::new())).unwrap(),
+        ];
+        let PluginSequence { seq, .. } = PluginSequence::new(&plugins, &config, vec![], false).unwrap();
+
+        let merges: Vec<_> = seq
+            .iter()
+            .filter_map(|action| match action.kind() {
+                ActionKind::Set(dst_key, _, merge) if action.id() == 0 => Some((dst_key.as_str(), *merge)),
+                _ => None,
+            })
+            .collect();
+        assert!(merges.contains(&("next_version", MergeStrategy::RequireEqual)));
+        assert!(merges.contains(&("files_to_commit", MergeStrategy::ConcatUnique)));
+    }
+
+    #[test]
+    fn release_tag_provided_before_prepare() {
+        use crate::builtin_plugins::{ClogPlugin, GitPlugin};
+
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+            clog = "builtin"
+
+            [steps]
+            pre_flight = [ "git", "clog" ]
+            get_last_release = "git"
+            derive_next_version = [ "clog" ]
+            generate_notes = "clog"
+            prepare = [ "clog" ]
+            verify_release = [ "git" ]
+            commit = "git"
+        "#;
+        let config = toml::from_str(toml).unwrap();
+
+        // The next version is provisioned to git before the release tag is queried, whatever the plugin order
+        for &(git, clog) in &[(0, 1), (1, 0)] {
+            let mut plugins = vec![
+                Plugin::new(Box::new(GitPlugin::new())).unwrap(),
+                Plugin::new(Box::new(ClogPlugin::new())).unwrap(),
+            ];
+            if git == 1 {
+                plugins.reverse();
+            }
+            let PluginSequence { seq, .. } = PluginSequence::new(&plugins, &config, vec![], false).unwrap();
+            let prepare = seq
+                .iter()
+                .position(|a| a == &Action::call(clog, PluginStep::Prepare))
+                .unwrap();
+            let position = |action: Action| seq[..prepare].iter().rposition(|a| a == &action).unwrap();
+
+            let version_set = position(Action::set_merged(
+                git,
+                "next_version",
+                "next_version",
+                MergeStrategy::MaxSemver,
+            ));
+            let tag_get = position(Action::get(git, "release_tag"));
+            let tag_set = position(Action::set(clog, "release_tag", "release_tag"));
+            assert!(version_set < tag_get && tag_get < tag_set);
+
+            // Before Derive Next Version the release tag isn't queried at all
+            let derive = seq
+                .iter()
+                .position(|a| a == &Action::call(clog, PluginStep::DeriveNextVersion))
+                .unwrap();
+            assert!(!seq[..derive].contains(&Action::get(git, "release_tag")));
+        }
+    }
+
+    #[test]
+    fn optional_key_without_provider() {
+        use crate::builtin_plugins::ClogPlugin;
+
+        let toml = r#"
+            [plugins]
+            clog = "builtin"
+
+            [steps]
+            derive_next_version = "clog"
+            generate_notes = "clog"
+
+            [cfg]
+            current_version = { rev = "v1.0.0", semver = "1.0.0" }
+        "#;
+
+        let mut config: Config = toml::from_str(toml).unwrap();
+        let plugins = vec![Plugin::new(Box::new(ClogPlugin::new())).unwrap()];
+        let PluginSequence { seq, .. } = PluginSequence::new(&plugins, &config, vec![], true).unwrap();
+        assert!(seq.contains(&Action::require_config_entry(0, "current_version")));
+        assert!(!seq.contains(&Action::require_config_entry(0, "git_remote_url")));
+
+        // The optional value is still taken from releaserc.toml if it's defined there
+        config.cfg.insert(
+            "git_remote_url".into(),
+            ValueDefinition::Value("https://github.com/o/r.git".into()),
+        );
+        let PluginSequence { seq, .. } = PluginSequence::new(&plugins, &config, vec![], true).unwrap();
+        assert!(seq.contains(&Action::require_config_entry(0, "git_remote_url")));
+    }
+
     mod resolve {
         use super::*;
 
@@ -1,15 +1,20 @@
+use std::ops::Try;
+
 use failure::Fail;
 use strum::IntoEnumIterator;
 
 use crate::config::{Config, Map, PluginDefinitionMap};
 use crate::logger;
+use crate::plugin_runtime::checkpoint::{Checkpoint, CHECKPOINT_PATH};
 use crate::plugin_runtime::data_mgr::DataManager;
-use crate::plugin_runtime::graph::{ActionKind, PluginSequence};
+use crate::plugin_runtime::graph::{Action, ActionKind, PluginSequence};
+use crate::plugin_runtime::plan::Plan;
 use crate::plugin_runtime::resolver::PluginResolver;
 use crate::plugin_runtime::starter::PluginStarter;
 use crate::plugin_support::flow::Value;
 use crate::plugin_support::{Plugin, PluginInterface, PluginStep, RawPlugin, RawPluginState};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 pub type PluginId = usize;
 
@@ -19,6 +24,10 @@ pub struct Kernel {
     sequence: PluginSequence,
     env: HashMap<String, String>,
     is_dry_run: bool,
+    keep_progress: bool,
+    resume_from: Option<Checkpoint>,
+    is_resumed: bool,
+    checkpoint_path: PathBuf,
 }
 
 impl Kernel {
@@ -26,70 +35,40 @@ impl Kernel {
         KernelBuilder::new(config)
     }
 
+    /// Describe the actions `run` would perform, without running any plugin step
+    pub fn plan(&self) -> Plan {
+        let names: Vec<String> = self.plugins.iter().map(|p| p.name.clone()).collect();
+        Plan::new(&names, self.sequence.capabilities(), self.sequence.steps())
+    }
+
     pub fn run(mut self) -> Result<(), failure::Error> {
-        for action in self.sequence.into_iter() {
+        let actions: Vec<Action> = self.sequence.iter().cloned().collect();
+        let sequence = self.fingerprint(&actions);
+        let checkpoint_path = &self.checkpoint_path.clone();
+
+        let start = match self.resume_from.take() {
+            Some(checkpoint) => self.restore(checkpoint, &actions, &sequence)?,
+            None => 0,
+        };
+
+        // Wet steps that ran, including the one that failed, in order of execution
+        let mut wet_steps = Vec::new();
+
+        for (idx, action) in actions.iter().cloned().enumerate().skip(start) {
             log::trace!("running action {:?}", action);
-            let id = action.id();
-            match action.into_kind() {
-                ActionKind::Call(step) => {
-                    let plugin = &self.plugins[id];
-                    log::debug!("call {}::{}", plugin.name, step.as_str());
-                    let _span = logger::span(&plugin.name);
-                    let mut callable = plugin.as_interface();
-                    match step {
-                        PluginStep::PreFlight => callable.pre_flight()?,
-                        PluginStep::GetLastRelease => callable.get_last_release()?,
-                        PluginStep::DeriveNextVersion => callable.derive_next_version()?,
-                        PluginStep::GenerateNotes => callable.generate_notes()?,
-                        PluginStep::Prepare => callable.prepare()?,
-                        PluginStep::VerifyRelease => callable.verify_release()?,
-                        PluginStep::Commit => callable.commit()?,
-                        PluginStep::Publish => callable.publish()?,
-                        PluginStep::Notify => callable.notify()?,
-                    }
-                }
-                ActionKind::Get(src_key) => {
-                    let plugin = &self.plugins[id];
-                    let span = logger::span(&plugin.name);
-                    let value = plugin.as_interface().get_value(&src_key)?;
-                    drop(span);
-                    log::debug!("get {}::{} ==> {:?}", self.plugins[id].name, src_key, value);
-                    let value = Value::builder(&src_key).value(value).build();
-                    self.data_mgr.insert_global(src_key, value);
-                }
-                ActionKind::Set(dst_key, src_key) => {
-                    let value = self.data_mgr.prepare_value(id, &dst_key, &src_key)?;
-                    log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
-                    let plugin = &self.plugins[id];
-                    let _span = logger::span(&plugin.name);
-                    plugin.as_interface().set_value(&dst_key, value)?;
-                }
-                ActionKind::SetValue(dst_key, value) => {
-                    let value = Value::builder(&dst_key).value(value).build();
-                    log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
-                    let plugin = &self.plugins[id];
-                    let _span = logger::span(&plugin.name);
-                    self.plugins[id].as_interface().set_value(&dst_key, value)?;
-                }
-                ActionKind::RequireConfigEntry(dst_key) => {
-                    let value = self.data_mgr.prepare_value_same_key(id, &dst_key)?;
-                    log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
-                    let plugin = &self.plugins[id];
-                    let _span = logger::span(&plugin.name);
-                    self.plugins[id].as_interface().set_value(&dst_key, value)?;
-                }
-                ActionKind::RequireEnvValue(dst_key, src_key) => {
-                    let value = self
-                        .env
-                        .get(&src_key)
-                        .ok_or_else(|| Error::EnvValueUndefined(src_key.clone()))?;
-                    let value = Value::builder(&src_key).value(serde_json::to_value(value)?).build();
-                    log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
-                    let plugin = &self.plugins[id];
-                    let _span = logger::span(&plugin.name);
-                    self.plugins[id].as_interface().set_value(&dst_key, value)?;
-                }
+            if let Err(err) = self.run_action(idx, action, &mut wet_steps) {
+                let resume_from = self.resume_point(idx, &wet_steps);
+                self.rollback(&wet_steps, resume_from);
+                // Rolled back steps have to be run again on resume
+                self.save_checkpoint(checkpoint_path, &sequence, resume_from.checked_sub(1));
+                return Err(err);
             }
+
+            self.save_checkpoint(checkpoint_path, &sequence, Some(idx));
+        }
+
+        if !self.is_dry_run {
+            Checkpoint::remove(checkpoint_path)?;
         }
 
         if self.is_dry_run {
@@ -101,6 +80,234 @@ impl Kernel {
 
         Ok(())
     }
+
+    fn run_action(
+        &mut self,
+        idx: usize,
+        action: Action,
+        wet_steps: &mut Vec<(usize, PluginId, PluginStep)>,
+    ) -> Result<(), failure::Error> {
+        let id = action.id();
+        match action.into_kind() {
+            ActionKind::Call(step) => {
+                let plugin = &self.plugins[id];
+                log::debug!("call {}::{}", plugin.name, step.as_str());
+                let _span = logger::span(&plugin.name);
+                let mut callable = plugin.as_interface();
+                // Recorded before the call, so a step failing halfway gets to clean up on rollback
+                if step.is_wet() {
+                    wet_steps.push((idx, id, step));
+                }
+                match step {
+                    PluginStep::PreFlight => callable.pre_flight()?,
+                    PluginStep::GetLastRelease => callable.get_last_release()?,
+                    PluginStep::DeriveNextVersion => callable.derive_next_version()?,
+                    PluginStep::GenerateNotes => callable.generate_notes()?,
+                    PluginStep::Prepare => callable.prepare()?,
+                    PluginStep::VerifyRelease => callable.verify_release()?,
+                    PluginStep::Commit => callable.commit()?,
+                    PluginStep::Publish => callable.publish()?,
+                    PluginStep::Notify => callable.notify()?,
+                }
+            }
+            ActionKind::Get(src_key) => {
+                let plugin = &self.plugins[id];
+                let span = logger::span(&plugin.name);
+                let value = match plugin.as_interface().get_value(&src_key).into_result() {
+                    Ok(value) => value,
+                    // Plugin state from the previous run is lost, but the value is already in the checkpoint
+                    Err(err) if self.is_resumed && self.data_mgr.has_global(&src_key) => {
+                        log::debug!(
+                            "get {}::{} failed, using the checkpoint value: {}",
+                            plugin.name,
+                            src_key,
+                            err
+                        );
+                        return Ok(());
+                    }
+                    Err(err) => return Err(err),
+                };
+                drop(span);
+                log::debug!("get {}::{} ==> {:?}", self.plugins[id].name, src_key, value);
+                let value = Value::builder(&src_key).value(value).build();
+                self.data_mgr.insert_global(&self.plugins[id].name, src_key, value);
+            }
+            ActionKind::Set(dst_key, src_key, merge) => {
+                let value = self.data_mgr.prepare_value(id, &dst_key, &src_key, merge)?;
+                log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
+                let plugin = &self.plugins[id];
+                let _span = logger::span(&plugin.name);
+                plugin.as_interface().set_value(&dst_key, value)?;
+            }
+            ActionKind::SetValue(dst_key, value) => {
+                let value = Value::builder(&dst_key).value(value).build();
+                log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
+                let plugin = &self.plugins[id];
+                let _span = logger::span(&plugin.name);
+                self.plugins[id].as_interface().set_value(&dst_key, value)?;
+            }
+            ActionKind::RequireConfigEntry(dst_key) => {
+                let value = self.data_mgr.prepare_value_same_key(id, &dst_key)?;
+                log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
+                let plugin = &self.plugins[id];
+                let _span = logger::span(&plugin.name);
+                self.plugins[id].as_interface().set_value(&dst_key, value)?;
+            }
+            ActionKind::RequireEnvValue(dst_key, src_key) => {
+                let value = self
+                    .env
+                    .get(&src_key)
+                    .ok_or_else(|| Error::EnvValueUndefined(src_key.clone()))?;
+                let value = Value::builder(&src_key).value(serde_json::to_value(value)?).build();
+                log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
+                let plugin = &self.plugins[id];
+                let _span = logger::span(&plugin.name);
+                self.plugins[id].as_interface().set_value(&dst_key, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restore the data and plugin configurations from the checkpoint
+    /// and return the index of the action to continue from
+    fn restore(
+        &mut self,
+        checkpoint: Checkpoint,
+        actions: &[Action],
+        sequence: &[String],
+    ) -> Result<usize, failure::Error> {
+        checkpoint.check_sequence(sequence)?;
+
+        let start = checkpoint.next_action();
+        log::info!("resuming the release from action {} of {}", start, actions.len());
+
+        self.data_mgr.restore_globals(checkpoint.globals);
+
+        for plugin in &self.plugins {
+            if let Some(config) = checkpoint.configs.get(&plugin.name) {
+                let _span = logger::span(&plugin.name);
+                plugin.as_interface().set_config(config.clone())?;
+            }
+        }
+
+        // Values from the environment are never saved to the checkpoint, so they are loaded again
+        for action in &actions[..start] {
+            if let ActionKind::RequireEnvValue(..) = action.kind() {
+                self.run_action(0, action.clone(), &mut Vec::new())?;
+            }
+        }
+
+        // Plugins build their runtime state on pre-flight, so they have to restore it before continuing
+        for action in &actions[..start] {
+            if let ActionKind::Call(PluginStep::PreFlight) = action.kind() {
+                let plugin = &self.plugins[action.id()];
+                log::debug!("resume {}", plugin.name);
+                let _span = logger::span(&plugin.name);
+                plugin.as_interface().resume()?;
+            }
+        }
+
+        self.is_resumed = true;
+
+        Ok(start)
+    }
+
+    /// Actions along with the names of their plugins, identifying the sequence a checkpoint was made for
+    fn fingerprint(&self, actions: &[Action]) -> Vec<String> {
+        actions
+            .iter()
+            .map(|action| format!("{} {:?}", self.plugins[action.id()].name, action.kind()))
+            .collect()
+    }
+
+    /// Persist the release progress, dry runs are never persisted
+    fn save_checkpoint(&self, path: &Path, sequence: &[String], last_completed_action: Option<usize>) {
+        if self.is_dry_run {
+            return;
+        }
+
+        let result = || -> Result<(), failure::Error> {
+            let mut configs = Map::new();
+            for (id, plugin) in self.plugins.iter().enumerate() {
+                let mut config = plugin.as_interface().get_config()?;
+                // Values from the environment are usually secrets, they are provisioned again on resume
+                for action in self.sequence.iter().filter(|action| action.id() == id) {
+                    if let ActionKind::RequireEnvValue(dst_key, src_key) = action.kind() {
+                        if let Some(value) = config.get_mut(dst_key) {
+                            *value = serde_json::to_value(Value::<serde_json::Value>::load_from_env(src_key))?;
+                        }
+                    }
+                }
+                configs.insert(plugin.name.clone(), config);
+            }
+
+            let checkpoint = Checkpoint {
+                sequence: sequence.to_vec(),
+                last_completed_action,
+                globals: self.data_mgr.globals().clone(),
+                configs,
+            };
+
+            checkpoint.save(path)
+        }();
+
+        if let Err(err) = result {
+            log::warn!(
+                "failed to save checkpoint {}, resuming won't be possible: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+
+    /// Index of the action to resume from after the action at `failed` failed
+    ///
+    /// Plugins that ran a wet step since then are rolled back, so all of their wet steps have to run again.
+    /// With `keep_progress` the plugins that succeeded before the failed one are not rolled back.
+    fn resume_point(&self, failed: usize, wet_steps: &[(usize, PluginId, PluginStep)]) -> usize {
+        let mut resume_from = match wet_steps.first() {
+            Some(&(idx, _, _)) if !self.keep_progress => idx,
+            _ => failed,
+        };
+
+        loop {
+            let earliest = wet_steps
+                .iter()
+                .filter(|&&(_, id, _)| {
+                    wet_steps
+                        .iter()
+                        .any(|&(idx, other, _)| other == id && idx >= resume_from)
+                })
+                .map(|&(idx, _, _)| idx)
+                .min()
+                .unwrap_or(resume_from);
+
+            if earliest >= resume_from {
+                return resume_from;
+            }
+            resume_from = earliest;
+        }
+    }
+
+    /// Roll back every plugin that ran a wet step since `resume_from`, in reverse order
+    fn rollback(&mut self, wet_steps: &[(usize, PluginId, PluginStep)], resume_from: usize) {
+        let mut rolled_back = Vec::new();
+
+        for &(_, id, step) in wet_steps.iter().rev().filter(|&&(idx, _, _)| idx >= resume_from) {
+            if rolled_back.contains(&id) {
+                continue;
+            }
+            rolled_back.push(id);
+
+            let plugin = &self.plugins[id];
+            log::warn!("rolling back {} (last step: {})", plugin.name, step.as_str());
+            let _span = logger::span(&plugin.name);
+            if let Err(err) = plugin.as_interface().rollback().into_result() {
+                log::error!("rollback failed: {}", err);
+            }
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -112,6 +319,9 @@ pub enum InjectionTarget {
 pub struct KernelBuilder {
     config: Config,
     injections: Vec<(Box<dyn PluginInterface>, InjectionTarget)>,
+    resume: bool,
+    keep_progress: bool,
+    checkpoint_path: PathBuf,
 }
 
 impl KernelBuilder {
@@ -119,9 +329,31 @@ impl KernelBuilder {
         KernelBuilder {
             config,
             injections: Vec::new(),
+            resume: false,
+            keep_progress: false,
+            checkpoint_path: PathBuf::from(CHECKPOINT_PATH),
         }
     }
 
+    /// Continue the release from the checkpoint left by a failed run
+    pub fn resume(&mut self, resume: bool) -> &mut Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Only roll back the plugin whose step failed when an action fails,
+    /// keeping the wet steps that succeeded before it for `resume`
+    pub fn keep_progress(&mut self, keep_progress: bool) -> &mut Self {
+        self.keep_progress = keep_progress;
+        self
+    }
+
+    /// Where the release progress is persisted, `.semantic-rs/state.json` by default
+    pub fn checkpoint_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.checkpoint_path = path.into();
+        self
+    }
+
     pub fn inject_plugin<P: PluginInterface + 'static>(&mut self, plugin: P, target: InjectionTarget) -> &mut Self {
         let plugin = Box::new(plugin);
         self.injections.push((plugin, target));
@@ -142,7 +374,7 @@ impl KernelBuilder {
         let plugins = Self::plugin_def_map_to_vec(plugins);
 
         // Resolve stage
-        let plugins = Self::resolve_plugins(plugins)?;
+        let plugins = Self::resolve_plugins(plugins, &self.config.dir)?;
         Self::check_all_resolved(&plugins)?;
         log::debug!("all plugins resolved");
 
@@ -172,12 +404,23 @@ impl KernelBuilder {
         // Create data manager
         let data_mgr = DataManager::new(&self.config);
 
+        // Load the checkpoint to resume from
+        let resume_from = if self.resume {
+            Some(Checkpoint::load(&self.checkpoint_path)?)
+        } else {
+            None
+        };
+
         Ok(Kernel {
             env: std::env::vars().collect(),
             plugins,
             data_mgr,
             sequence,
             is_dry_run,
+            keep_progress: self.keep_progress,
+            resume_from,
+            is_resumed: false,
+            checkpoint_path: self.checkpoint_path.clone(),
         })
     }
 
@@ -188,9 +431,9 @@ impl KernelBuilder {
             .collect()
     }
 
-    fn resolve_plugins(plugins: Vec<RawPlugin>) -> Result<Vec<RawPlugin>, failure::Error> {
+    fn resolve_plugins(plugins: Vec<RawPlugin>, project_root: &Path) -> Result<Vec<RawPlugin>, failure::Error> {
         log::debug!("resolving plugins...");
-        let resolver = PluginResolver::new();
+        let resolver = PluginResolver::new(project_root);
         let plugins = plugins
             .into_iter()
             .map(|p| resolver.resolve(p))
@@ -245,3 +488,277 @@ pub enum Error {
     #[fail(display = "environment value must be set: {}", _0)]
     EnvValueUndefined(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_runtime::checkpoint;
+    use crate::plugin_support::proto::response::{self, PluginResponse};
+    use crate::utils::TempDir;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Plugin recording its calls to the shared log, failing on the given step
+    /// or if its config wasn't fully provisioned
+    struct Stub {
+        name: String,
+        log: Log,
+        fail_on: Option<PluginStep>,
+        config: serde_json::Value,
+    }
+
+    impl Stub {
+        fn new(name: &str, log: &Log, fail_on: Option<PluginStep>) -> Self {
+            Stub {
+                name: name.into(),
+                log: log.clone(),
+                fail_on,
+                config: serde_json::json!({}),
+            }
+        }
+
+        /// Require the `TOKEN` environment value as `token`
+        fn with_token(mut self) -> Self {
+            let token = Value::<serde_json::Value>::load_from_env("TOKEN");
+            self.config = serde_json::json!({ "token": token });
+            self
+        }
+
+        fn record(&mut self, call: &str) -> Result<(), failure::Error> {
+            self.log.borrow_mut().push(format!("{}::{}", self.name, call));
+            let config: Map<String, Value<serde_json::Value>> = serde_json::from_value(self.config.clone())?;
+            if !config.values().all(Value::is_ready) {
+                return Err(failure::err_msg("config not provisioned"));
+            }
+            Ok(())
+        }
+
+        fn step(&mut self, step: PluginStep) -> response::Null {
+            self.record(step.as_str())?;
+            if self.fail_on == Some(step) {
+                return PluginResponse::from_error(failure::err_msg("step failed"));
+            }
+            PluginResponse::from_ok(())
+        }
+    }
+
+    impl PluginInterface for Stub {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok(self.name.clone())
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(PluginStep::iter().collect())
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(self.config.clone())
+        }
+
+        fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+            self.config = config;
+            PluginResponse::from_ok(())
+        }
+
+        fn pre_flight(&mut self) -> response::Null {
+            self.step(PluginStep::PreFlight)
+        }
+
+        fn resume(&mut self) -> response::Null {
+            PluginResponse::from_ok(self.record("resume")?)
+        }
+
+        fn commit(&mut self) -> response::Null {
+            self.step(PluginStep::Commit)
+        }
+
+        fn publish(&mut self) -> response::Null {
+            self.step(PluginStep::Publish)
+        }
+
+        fn rollback(&mut self) -> response::Null {
+            self.log.borrow_mut().push(format!("{}::rollback", self.name));
+            PluginResponse::from_ok(())
+        }
+    }
+
+    const PUBLISH: &str = r#"[ "github", "docker" ]"#;
+
+    fn kernel(plugins: Vec<Stub>, checkpoint_path: &Path, resume_from: Option<Checkpoint>) -> Kernel {
+        kernel_publishing(PUBLISH, plugins, checkpoint_path, resume_from)
+    }
+
+    fn kernel_publishing(
+        publish: &str,
+        plugins: Vec<Stub>,
+        checkpoint_path: &Path,
+        resume_from: Option<Checkpoint>,
+    ) -> Kernel {
+        let toml = format!(
+            r#"
+            [plugins]
+            git = "builtin"
+            github = "builtin"
+            docker = "builtin"
+
+            [steps]
+            pre_flight = [ "git" ]
+            commit = "git"
+            publish = {}
+        "#,
+            publish
+        );
+        let config: Config = toml::from_str(&toml).unwrap();
+        let plugins: Vec<Plugin> = plugins
+            .into_iter()
+            .map(|stub| Plugin::new(Box::new(stub)).unwrap())
+            .collect();
+        let sequence = PluginSequence::new(&plugins, &config, vec![], false).unwrap();
+
+        Kernel {
+            plugins,
+            data_mgr: DataManager::new(&config),
+            sequence,
+            env: vec![("TOKEN".to_owned(), "super-secret-token".to_owned())]
+                .into_iter()
+                .collect(),
+            is_dry_run: false,
+            keep_progress: false,
+            resume_from,
+            is_resumed: false,
+            checkpoint_path: checkpoint_path.to_owned(),
+        }
+    }
+
+    #[test]
+    fn rollback_in_reverse_order() {
+        let log = Log::default();
+        let dir = TempDir::new("kernel-rollback");
+        let path = dir.join("state.json");
+        let plugins = vec![
+            Stub::new("git", &log, None),
+            Stub::new("github", &log, None),
+            Stub::new("docker", &log, Some(PluginStep::Publish)),
+        ];
+
+        assert!(kernel(plugins, &path, None).run().is_err());
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "git::pre_flight",
+                "git::commit",
+                "github::publish",
+                "docker::publish",
+                "docker::rollback",
+                "github::rollback",
+                "git::rollback",
+            ]
+        );
+    }
+
+    #[test]
+    fn rollback_failed_step() {
+        let log = Log::default();
+        let dir = TempDir::new("kernel-rollback-failed");
+        let path = dir.join("state.json");
+        let plugins = vec![
+            Stub::new("git", &log, Some(PluginStep::Commit)),
+            Stub::new("github", &log, None),
+            Stub::new("docker", &log, None),
+        ];
+
+        assert!(kernel(plugins, &path, None).run().is_err());
+        assert_eq!(*log.borrow(), vec!["git::pre_flight", "git::commit", "git::rollback"]);
+    }
+
+    #[test]
+    fn checkpoint_without_env_values() {
+        let log = Log::default();
+        let dir = TempDir::new("kernel-env");
+        let path = dir.join("state.json");
+        let plugins = vec![
+            Stub::new("git", &log, None).with_token(),
+            Stub::new("github", &log, None).with_token(),
+            Stub::new("docker", &log, Some(PluginStep::Publish)),
+        ];
+
+        assert!(kernel(plugins, &path, None).run().is_err());
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("super-secret-token"), "{}", contents);
+    }
+
+    #[test]
+    fn resume_remaining_steps() {
+        let log = Log::default();
+        let dir = TempDir::new("kernel-resume");
+        let path = dir.join("state.json");
+        let plugins = vec![
+            Stub::new("git", &log, None).with_token(),
+            Stub::new("github", &log, None),
+            Stub::new("docker", &log, Some(PluginStep::Publish)),
+        ];
+
+        // Only the failed plugin is rolled back, the release commit and the GitHub release are kept
+        let mut first_run = kernel(plugins, &path, None);
+        first_run.keep_progress = true;
+        assert!(first_run.run().is_err());
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "git::pre_flight",
+                "git::commit",
+                "github::publish",
+                "docker::publish",
+                "docker::rollback",
+            ]
+        );
+
+        log.borrow_mut().clear();
+        let plugins = vec![
+            Stub::new("git", &log, None).with_token(),
+            Stub::new("github", &log, None),
+            Stub::new("docker", &log, None),
+        ];
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        kernel(plugins, &path, Some(checkpoint)).run().unwrap();
+
+        assert_eq!(*log.borrow(), vec!["git::resume", "docker::publish"]);
+        assert!(!path.exists());
+    }
+    #[test]
+    fn resume_refused_for_changed_sequence() {
+        let log = Log::default();
+        let dir = TempDir::new("kernel-changed-sequence");
+        let path = dir.join("state.json");
+        let plugins = vec![
+            Stub::new("git", &log, None).with_token(),
+            Stub::new("github", &log, None),
+            Stub::new("docker", &log, Some(PluginStep::Publish)),
+        ];
+
+        let mut first_run = kernel(plugins, &path, None);
+        first_run.keep_progress = true;
+        assert!(first_run.run().is_err());
+
+        // Same number of actions, but the plugins publish in a different order
+        log.borrow_mut().clear();
+        let plugins = vec![
+            Stub::new("git", &log, None).with_token(),
+            Stub::new("github", &log, None),
+            Stub::new("docker", &log, None),
+        ];
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        let error = kernel_publishing(r#"[ "docker", "github" ]"#, plugins, &path, Some(checkpoint))
+            .run()
+            .unwrap_err();
+
+        match error.downcast_ref::<checkpoint::Error>() {
+            Some(checkpoint::Error::SequenceChanged(..)) => (),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(log.borrow().is_empty());
+        assert!(path.exists());
+    }
+}
@@ -1,8 +1,12 @@
+pub mod checkpoint;
 pub mod discovery;
 //pub mod dispatcher;
 pub mod data_mgr;
+pub mod dataflow;
 pub mod graph;
 pub mod kernel;
+pub mod plan;
+pub mod process;
 pub mod resolver;
 pub mod starter;
 
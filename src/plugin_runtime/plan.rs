@@ -0,0 +1,342 @@
+//! Human- and machine-readable view of the resolved action sequence
+//!
+//! The plan lists the steps in the order of execution, plugins called on each step,
+//! keys flowing between plugins and values that must be supplied from the environment or `releaserc.toml`.
+//! Plain configuration values passed to plugins as is are not part of the plan.
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::config::Map;
+use crate::plugin_runtime::graph::{Action, ActionKind};
+use crate::plugin_support::flow::{Availability, MergeStrategy, ProvisionCapability};
+use crate::plugin_support::PluginStep;
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Plan {
+    pub steps: Vec<StepPlan>,
+    pub required_env: Vec<EnvRequirement>,
+    pub required_config: Vec<ConfigRequirement>,
+    /// Keys the plugins provide and when they become available
+    pub provisions: Vec<Provision>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StepPlan {
+    pub step: PluginStep,
+    /// Plugins called on this step, in order
+    pub plugins: Vec<String>,
+    pub data_flow: Vec<KeyFlow>,
+}
+
+/// Key provided by one or more plugins and passed to another plugin
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct KeyFlow {
+    pub from: Vec<String>,
+    pub source_key: String,
+    pub to: String,
+    pub dest_key: String,
+    pub merge: MergeStrategy,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Provision {
+    pub plugin: String,
+    pub key: String,
+    pub when: Availability,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EnvRequirement {
+    pub plugin: String,
+    pub key: String,
+    pub env: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ConfigRequirement {
+    pub plugin: String,
+    pub key: String,
+    /// First step the entry is required at
+    pub step: PluginStep,
+}
+
+impl Plan {
+    pub fn new<'a>(
+        names: &[String],
+        caps: &[Vec<ProvisionCapability>],
+        steps: impl Iterator<Item = (PluginStep, &'a [Action])>,
+    ) -> Self {
+        let mut plan = Plan::default();
+        for (name, caps) in names.iter().zip(caps) {
+            plan.provisions.extend(caps.iter().map(|cap| Provision {
+                plugin: name.clone(),
+                key: cap.key.clone(),
+                when: cap.when,
+            }));
+        }
+
+        // Plugins that provided the key the last time it was requested
+        let mut providers: Map<&str, Vec<String>> = Map::new();
+
+        for (step, actions) in steps {
+            let mut step_plan = StepPlan {
+                step,
+                plugins: Vec::new(),
+                data_flow: Vec::new(),
+            };
+            let mut step_providers: Map<&str, Vec<String>> = Map::new();
+
+            for action in actions {
+                let name = &names[action.id()];
+                match action.kind() {
+                    ActionKind::Call(_) => step_plan.plugins.push(name.clone()),
+                    ActionKind::Get(src_key) => {
+                        let entry = step_providers.entry(src_key.as_str()).or_default();
+                        if !entry.contains(name) {
+                            entry.push(name.clone());
+                        }
+                    }
+                    ActionKind::Set(dst_key, src_key, merge) => {
+                        let from = step_providers
+                            .get(src_key.as_str())
+                            .or_else(|| providers.get(src_key.as_str()))
+                            .cloned()
+                            .unwrap_or_default();
+                        step_plan.data_flow.push(KeyFlow {
+                            from,
+                            source_key: src_key.clone(),
+                            to: name.clone(),
+                            dest_key: dst_key.clone(),
+                            merge: *merge,
+                        });
+                    }
+                    ActionKind::SetValue(..) => (),
+                    ActionKind::RequireConfigEntry(key) => {
+                        if !plan
+                            .required_config
+                            .iter()
+                            .any(|req| &req.plugin == name && &req.key == key)
+                        {
+                            plan.required_config.push(ConfigRequirement {
+                                plugin: name.clone(),
+                                key: key.clone(),
+                                step,
+                            });
+                        }
+                    }
+                    ActionKind::RequireEnvValue(dst_key, env) => {
+                        let req = EnvRequirement {
+                            plugin: name.clone(),
+                            key: dst_key.clone(),
+                            env: env.clone(),
+                        };
+                        if !plan.required_env.contains(&req) {
+                            plan.required_env.push(req);
+                        }
+                    }
+                }
+            }
+
+            providers.extend(step_providers);
+            plan.steps.push(step_plan);
+        }
+
+        plan
+    }
+
+    /// When the key provided by the plugin becomes available
+    pub fn availability(&self, plugin: &str, key: &str) -> Option<Availability> {
+        self.provisions
+            .iter()
+            .find(|provision| provision.plugin == plugin && provision.key == key)
+            .map(|provision| provision.when)
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let steps = self
+            .steps
+            .iter()
+            .map(|s| vec![s.step.as_str().to_owned(), or_dash(s.plugins.join(", "))])
+            .collect();
+        write_table(f, "Steps", &["STEP", "PLUGINS"], steps)?;
+
+        let flows = self
+            .steps
+            .iter()
+            .flat_map(|s| {
+                s.data_flow.iter().map(move |flow| {
+                    let from = if flow.from.is_empty() {
+                        flow.source_key.clone()
+                    } else {
+                        flow.from
+                            .iter()
+                            .map(|p| format!("{}::{}", p, flow.source_key))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    };
+                    vec![
+                        s.step.as_str().to_owned(),
+                        from,
+                        format!("{}::{}", flow.to, flow.dest_key),
+                        flow.merge.as_str().to_owned(),
+                    ]
+                })
+            })
+            .collect();
+        write_table(f, "Data flow", &["STEP", "FROM", "TO", "MERGE"], flows)?;
+
+        let env = self
+            .required_env
+            .iter()
+            .map(|req| vec![req.env.clone(), format!("{}::{}", req.plugin, req.key)])
+            .collect();
+        write_table(f, "Required environment variables", &["ENV", "TO"], env)?;
+
+        let config = self
+            .required_config
+            .iter()
+            .map(|req| vec![format!("{}::{}", req.plugin, req.key), req.step.as_str().to_owned()])
+            .collect();
+        write_table(f, "Required configuration entries", &["KEY", "REQUIRED AT"], config)
+    }
+}
+
+fn or_dash(s: String) -> String {
+    if s.is_empty() {
+        "-".to_owned()
+    } else {
+        s
+    }
+}
+
+fn write_table(f: &mut fmt::Formatter, title: &str, header: &[&str], rows: Vec<Vec<String>>) -> fmt::Result {
+    writeln!(f, "{}:", title)?;
+
+    if rows.is_empty() {
+        return writeln!(f, "  (none)\n");
+    }
+
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = header.iter().map(|h| h.to_string()).collect();
+    for row in std::iter::once(header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(f, "  {}", line.trim_end())?;
+    }
+
+    writeln!(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        vec!["git".into(), "clog".into()]
+    }
+
+    fn plan() -> Plan {
+        let pre_flight = [
+            Action::set_value(0, "branch", "master"),
+            Action::require_env_value(0, "token", "GH_TOKEN"),
+            Action::call(0, PluginStep::PreFlight),
+            Action::call(1, PluginStep::PreFlight),
+        ];
+        let derive = [
+            Action::require_env_value(0, "token", "GH_TOKEN"),
+            Action::require_config_entry(1, "changelog"),
+            Action::get(0, "last_release"),
+            Action::set(1, "last_release", "last_release"),
+            Action::call(1, PluginStep::DeriveNextVersion),
+        ];
+        let prepare = [Action::set(1, "last_release", "last_release")];
+
+        let steps = [
+            (PluginStep::PreFlight, &pre_flight[..]),
+            (PluginStep::DeriveNextVersion, &derive[..]),
+            (PluginStep::Prepare, &prepare[..]),
+        ];
+
+        let caps = vec![
+            vec![ProvisionCapability::builder("last_release")
+                .after_step(PluginStep::GetLastRelease)
+                .build()],
+            vec![],
+        ];
+
+        Plan::new(&names(), &caps, steps.iter().cloned())
+    }
+
+    #[test]
+    fn plan_steps_and_flows() {
+        let plan = plan();
+
+        assert_eq!(plan.steps.len(), 3);
+        assert_eq!(plan.steps[0].plugins, vec!["git".to_owned(), "clog".to_owned()]);
+        assert!(plan.steps[0].data_flow.is_empty());
+        assert_eq!(plan.steps[1].plugins, vec!["clog".to_owned()]);
+
+        let flow = KeyFlow {
+            from: vec!["git".into()],
+            source_key: "last_release".into(),
+            to: "clog".into(),
+            dest_key: "last_release".into(),
+            merge: MergeStrategy::Concat,
+        };
+        assert_eq!(plan.steps[1].data_flow, vec![flow.clone()]);
+        // Values fetched on previous steps are remembered
+        assert_eq!(plan.steps[2].data_flow, vec![flow]);
+    }
+
+    #[test]
+    fn plan_requirements_are_deduplicated() {
+        let plan = plan();
+
+        assert_eq!(
+            plan.required_env,
+            vec![EnvRequirement {
+                plugin: "git".into(),
+                key: "token".into(),
+                env: "GH_TOKEN".into(),
+            }]
+        );
+        assert_eq!(
+            plan.required_config,
+            vec![ConfigRequirement {
+                plugin: "clog".into(),
+                key: "changelog".into(),
+                step: PluginStep::DeriveNextVersion,
+            }]
+        );
+    }
+
+    #[test]
+    fn plan_table() {
+        let table = plan().to_string();
+        assert!(table.contains("  derive_next_version  git::last_release  clog::last_release  concat"));
+        assert!(table.contains("  prepare              -"));
+        assert!(table.contains("  GH_TOKEN  git::token"));
+        assert!(table.contains("  clog::changelog  derive_next_version"));
+    }
+
+    #[test]
+    fn plan_json() {
+        let json = serde_json::to_value(plan()).unwrap();
+        assert_eq!(json["steps"][1]["step"], "derive_next_version");
+        assert_eq!(json["required_env"][0]["env"], "GH_TOKEN");
+        assert_eq!(json["provisions"][0]["when"]["after_step"], "get_last_release");
+    }
+}
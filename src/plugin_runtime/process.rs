@@ -0,0 +1,235 @@
+use std::cell::RefCell;
+use std::io::BufReader;
+use std::ops::Try;
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use failure::Fail;
+use serde::de::DeserializeOwned;
+
+use crate::plugin_support::proto::response::{self, PluginResponse};
+use crate::plugin_support::proto::rpc::{self, RpcClient};
+use crate::plugin_support::traits::describe;
+use crate::plugin_support::{PluginInterface, PluginStep};
+
+/// JSON-RPC error code for calls to methods the plugin doesn't implement
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// How long a plugin may take to exit after its stdin is closed before it's killed
+const EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Plugin running in a separate process and speaking JSON-RPC over stdio
+///
+/// See [rpc](crate::plugin_support::proto::rpc) for the protocol description.
+pub struct ProcessPlugin {
+    child: Child,
+    client: RefCell<Option<RpcClient<BufReader<ChildStdout>, ChildStdin>>>,
+    exit_timeout: Duration,
+}
+
+impl ProcessPlugin {
+    pub fn spawn(path: &Path) -> Result<Self, failure::Error> {
+        log::debug!("spawning plugin process {}", path.display());
+        Self::start(Command::new(path), &path.display().to_string())
+    }
+
+    fn start(mut command: Command, name: &str) -> Result<Self, failure::Error> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| Error::SpawnFailed(name.to_owned(), err.to_string()))?;
+
+        let stdin = child.stdin.take().ok_or(Error::StdioNotAttached)?;
+        let stdout = child.stdout.take().ok_or(Error::StdioNotAttached)?;
+
+        Ok(ProcessPlugin {
+            child,
+            client: RefCell::new(Some(RpcClient::new(BufReader::new(stdout), stdin))),
+            exit_timeout: EXIT_TIMEOUT,
+        })
+    }
+
+    /// Wait for the process to exit until the deadline, `None` if it's still running
+    fn wait_until(&mut self, deadline: Instant) -> std::io::Result<Option<std::process::ExitStatus>> {
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                return Ok(Some(status));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Option<serde_json::Value>) -> PluginResponse<T> {
+        let mut client = self.client.borrow_mut();
+        let client = client.as_mut().ok_or(Error::ProcessFinished)?;
+        client.call(method, params)
+    }
+
+    fn call_step(&self, step: PluginStep) -> response::Null {
+        self.call(step.as_str(), None)
+    }
+}
+
+impl Drop for ProcessPlugin {
+    fn drop(&mut self) {
+        // Closing the stdin signals the plugin to finish its work and exit
+        self.client.borrow_mut().take();
+        let deadline = Instant::now() + self.exit_timeout;
+        match self.wait_until(deadline) {
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                log::warn!(
+                    "plugin process {} didn't exit in {:?} after its stdin was closed, killing it",
+                    self.child.id(),
+                    self.exit_timeout
+                );
+                if let Err(err) = self.child.kill().and_then(|_| self.child.wait()) {
+                    log::error!("failed to kill plugin process: {}", err);
+                }
+            }
+            Err(err) => log::error!("failed to wait for plugin process to exit: {}", err),
+        }
+    }
+}
+
+impl PluginInterface for ProcessPlugin {
+    fn name(&self) -> response::Name {
+        self.call("name", None)
+    }
+
+    fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+        self.call("provision_capabilities", None)
+    }
+
+    fn get_value(&self, key: &str) -> response::GetValue {
+        self.call("get_value", Some(serde_json::json!({ "key": key })))
+    }
+
+    fn get_config(&self) -> response::Config {
+        self.call("get_config", None)
+    }
+
+    fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+        self.call("set_config", Some(serde_json::json!({ "config": config })))
+    }
+
+    fn methods(&self) -> response::Methods {
+        self.call("methods", None)
+    }
+
+    fn discover(&self) -> response::Discover {
+        match self.call("rpc.discover", None).into_result() {
+            Ok(document) => PluginResponse::from_ok(document),
+            Err(err) => match err.downcast_ref::<rpc::Error>() {
+                // Plugins without service discovery are described from their other methods
+                Some(rpc::Error::Remote(METHOD_NOT_FOUND, _)) => describe(self),
+                _ => PluginResponse::from_error(err),
+            },
+        }
+    }
+
+    fn pre_flight(&mut self) -> response::Null {
+        self.call_step(PluginStep::PreFlight)
+    }
+
+    fn get_last_release(&mut self) -> response::Null {
+        self.call_step(PluginStep::GetLastRelease)
+    }
+
+    fn derive_next_version(&mut self) -> response::Null {
+        self.call_step(PluginStep::DeriveNextVersion)
+    }
+
+    fn generate_notes(&mut self) -> response::Null {
+        self.call_step(PluginStep::GenerateNotes)
+    }
+
+    fn prepare(&mut self) -> response::Null {
+        self.call_step(PluginStep::Prepare)
+    }
+
+    fn verify_release(&mut self) -> response::Null {
+        self.call_step(PluginStep::VerifyRelease)
+    }
+
+    fn commit(&mut self) -> response::Null {
+        self.call_step(PluginStep::Commit)
+    }
+
+    fn publish(&mut self) -> response::Null {
+        self.call_step(PluginStep::Publish)
+    }
+
+    fn notify(&self) -> response::Null {
+        self.call_step(PluginStep::Notify)
+    }
+
+    fn resume(&mut self) -> response::Null {
+        match self.call("resume", None).into_result() {
+            Ok(()) => PluginResponse::from_ok(()),
+            Err(err) => match err.downcast_ref::<rpc::Error>() {
+                // Resume is optional, pre-flight restores the state as well
+                Some(rpc::Error::Remote(METHOD_NOT_FOUND, _)) => self.pre_flight(),
+                _ => PluginResponse::from_error(err),
+            },
+        }
+    }
+
+    fn rollback(&mut self) -> response::Null {
+        match self.call("rollback", None).into_result() {
+            Ok(()) => PluginResponse::from_ok(()),
+            Err(err) => match err.downcast_ref::<rpc::Error>() {
+                // Rollback is optional
+                Some(rpc::Error::Remote(METHOD_NOT_FOUND, _)) => PluginResponse::from_ok(()),
+                _ => PluginResponse::from_error(err),
+            },
+        }
+    }
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "failed to spawn plugin process {}: {}", _0, _1)]
+    SpawnFailed(String, String),
+    #[fail(display = "failed to attach to plugin process stdio")]
+    StdioNotAttached,
+    #[fail(display = "plugin process is already finished")]
+    ProcessFinished,
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell(script: &str) -> ProcessPlugin {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        ProcessPlugin::start(command, "sh").unwrap()
+    }
+
+    #[test]
+    fn call_child_process() {
+        let plugin = shell(r#"while read -r line; do echo '{"jsonrpc":"2.0","id":0,"result":{"data":"echo"}}'; done"#);
+        assert_eq!(plugin.name().into_result().unwrap(), "echo");
+
+        let start = Instant::now();
+        drop(plugin);
+        assert!(start.elapsed() < EXIT_TIMEOUT);
+    }
+
+    #[test]
+    fn kill_child_process_ignoring_eof() {
+        let mut plugin = shell("exec sleep 60");
+        plugin.exit_timeout = Duration::from_millis(100);
+
+        let start = Instant::now();
+        drop(plugin);
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+}
@@ -1,17 +1,28 @@
+use std::path::{Path, PathBuf};
+
 use failure::Fail;
 
+use crate::plugin_support::command::PipedCommand;
 use crate::plugin_support::{PluginInterface, RawPlugin, RawPluginState, ResolvedPlugin, UnresolvedPlugin};
 
+/// Directory where plugins installed from crates.io are kept
+const CARGO_PLUGINS_DIR: &str = ".semantic-rs/plugins";
+
 pub struct PluginResolver {
     builtin: BuiltinResolver,
     cargo: CargoResolver,
+    path: PathResolver,
+    binary: BinaryResolver,
 }
 
 impl PluginResolver {
-    pub fn new() -> Self {
+    /// `project_root` is the directory containing `releaserc.toml`
+    pub fn new(project_root: &Path) -> Self {
         PluginResolver {
             builtin: BuiltinResolver::new(),
             cargo: CargoResolver::new(),
+            path: PathResolver::new(project_root),
+            binary: BinaryResolver::new(),
         }
     }
 
@@ -24,8 +35,10 @@ impl PluginResolver {
         let meta = state.as_unresolved().unwrap();
 
         let new_meta = match meta {
-            UnresolvedPlugin::Builtin => self.builtin.resolve(&name, &meta)?,
-            UnresolvedPlugin::Cargo { .. } => self.cargo.resolve(&name, &meta)?,
+            UnresolvedPlugin::Builtin => self.builtin.resolve(&name, meta)?,
+            UnresolvedPlugin::Cargo { .. } => self.cargo.resolve(&name, meta)?,
+            UnresolvedPlugin::Path { .. } => self.path.resolve(&name, meta)?,
+            UnresolvedPlugin::Binary { .. } => self.binary.resolve(&name, meta)?,
         };
 
         Ok(RawPlugin::new(name, RawPluginState::Resolved(new_meta)))
@@ -59,17 +72,138 @@ impl Resolver for BuiltinResolver {
     }
 }
 
-struct CargoResolver;
+struct CargoResolver {
+    install_dir: PathBuf,
+}
 
 impl CargoResolver {
     pub fn new() -> CargoResolver {
-        CargoResolver
+        CargoResolver {
+            install_dir: PathBuf::from(CARGO_PLUGINS_DIR),
+        }
+    }
+
+    fn binary_path(root: &Path, package: &str) -> PathBuf {
+        root.join("bin")
+            .join(format!("{}{}", package, std::env::consts::EXE_SUFFIX))
     }
 }
 
 impl Resolver for CargoResolver {
-    fn resolve(&self, _name: &str, _meta: &UnresolvedPlugin) -> Result<ResolvedPlugin, failure::Error> {
-        unimplemented!()
+    fn resolve(&self, name: &str, meta: &UnresolvedPlugin) -> Result<ResolvedPlugin, failure::Error> {
+        let (package, version) = match meta {
+            UnresolvedPlugin::Cargo { package, version } => (package, version),
+            _ => unreachable!("CargoResolver can only resolve cargo plugins"),
+        };
+
+        // Every package version gets its own root, so different versions of the same plugin never clash
+        let root = self.install_dir.join(format!("{}-{}", package, version));
+        let binary = Self::binary_path(&root, package);
+
+        if binary.exists() {
+            log::debug!("plugin {} is already installed at {}", name, binary.display());
+        } else {
+            log::info!("Installing plugin {} ({} v{}), please wait...", name, package, version);
+            let root = root.display().to_string();
+            let args = &["install", "--root", &root, "--version", version, package];
+            PipedCommand::new("cargo", args)
+                .join(log::Level::Info)
+                .map_err(|err| Error::CargoInstallFailed(package.clone(), err.to_string()))?;
+        }
+
+        if !binary.exists() {
+            return Err(Error::PluginBinaryNotFound(name.to_owned(), binary.display().to_string()).into());
+        }
+
+        Ok(ResolvedPlugin::Process(binary))
+    }
+}
+
+struct PathResolver {
+    project_root: PathBuf,
+}
+
+impl PathResolver {
+    pub fn new(project_root: &Path) -> Self {
+        PathResolver {
+            project_root: project_root.to_owned(),
+        }
+    }
+}
+
+impl Resolver for PathResolver {
+    fn resolve(&self, name: &str, meta: &UnresolvedPlugin) -> Result<ResolvedPlugin, failure::Error> {
+        let path = match meta {
+            UnresolvedPlugin::Path { path } => self.project_root.join(path),
+            _ => unreachable!("PathResolver can only resolve path plugins"),
+        };
+
+        if !path.is_file() {
+            return Err(Error::PluginBinaryNotFound(name.to_owned(), path.display().to_string()).into());
+        }
+
+        if !is_executable(&path) {
+            return Err(Error::PluginBinaryNotExecutable(name.to_owned(), path.display().to_string()).into());
+        }
+
+        log::debug!("plugin {} resolved to {}", name, path.display());
+
+        Ok(ResolvedPlugin::Process(path))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|meta| meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+struct BinaryResolver;
+
+impl BinaryResolver {
+    pub fn new() -> Self {
+        BinaryResolver
+    }
+
+    /// Look the command up the same way a shell would:
+    /// commands containing a path separator are used as is, others are searched in $PATH
+    fn find_command(command: &str) -> Option<PathBuf> {
+        let path = Path::new(command);
+        if path.components().count() > 1 {
+            return Some(path.to_owned()).filter(|p| p.is_file());
+        }
+
+        let search_path = std::env::var_os("PATH")?;
+        std::env::split_paths(&search_path)
+            .flat_map(|dir| {
+                let plain = dir.join(command);
+                let with_suffix = dir.join(format!("{}{}", command, std::env::consts::EXE_SUFFIX));
+                vec![plain, with_suffix]
+            })
+            .find(|candidate| candidate.is_file())
+    }
+}
+
+impl Resolver for BinaryResolver {
+    fn resolve(&self, name: &str, meta: &UnresolvedPlugin) -> Result<ResolvedPlugin, failure::Error> {
+        let command = match meta {
+            UnresolvedPlugin::Binary { command } => command,
+            _ => unreachable!("BinaryResolver can only resolve binary plugins"),
+        };
+
+        let path = Self::find_command(command)
+            .ok_or_else(|| Error::PluginCommandNotFound(name.to_owned(), command.clone()))?;
+
+        log::debug!("plugin {} resolved to {}", name, path.display());
+
+        Ok(ResolvedPlugin::Process(path))
     }
 }
 
@@ -77,4 +211,47 @@ impl Resolver for CargoResolver {
 pub enum Error {
     #[fail(display = "{} is not registered as built-in plugin", _0)]
     BuiltinNotRegistered(String),
+    #[fail(display = "failed to install {} with cargo: {}", _0, _1)]
+    CargoInstallFailed(String, String),
+    #[fail(display = "binary for plugin {} not found at {}", _0, _1)]
+    PluginBinaryNotFound(String, String),
+    #[fail(display = "binary for plugin {} at {} is not executable", _0, _1)]
+    PluginBinaryNotExecutable(String, String),
+    #[fail(display = "command {:?} for plugin {} not found in PATH", _1, _0)]
+    PluginCommandNotFound(String, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    #[cfg(unix)]
+    fn resolve_path_relative_to_project_root() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = TempDir::new("resolver");
+        let plugin = root.join("tools/my-plugin");
+        std::fs::create_dir_all(plugin.parent().unwrap()).unwrap();
+        std::fs::write(&plugin, "#!/bin/sh\n").unwrap();
+
+        let resolver = PathResolver::new(&root);
+        let resolve = |path: &str| {
+            let meta = UnresolvedPlugin::Path { path: path.into() };
+            resolver.resolve("my-plugin", &meta).map_err(|err| err.to_string())
+        };
+
+        let err = resolve("./tools/my-plugin").err().unwrap();
+        assert!(err.contains("is not executable"), "{}", err);
+
+        std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        match resolve("./tools/my-plugin").unwrap() {
+            ResolvedPlugin::Process(path) => assert_eq!(path, root.join("./tools/my-plugin")),
+            ResolvedPlugin::Builtin(_) => panic!("path plugin resolved as a builtin"),
+        }
+
+        let err = resolve("./tools/missing").err().unwrap();
+        assert!(err.contains("not found"), "{}", err);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::plugin_runtime::process::ProcessPlugin;
 use crate::plugin_support::{Plugin, RawPlugin, RawPluginState, ResolvedPlugin};
 
 pub struct PluginStarter {}
@@ -15,6 +16,7 @@ impl PluginStarter {
             RawPluginState::Unresolved(_) => panic!("all plugins must be resolved before calling Starter::start"),
             RawPluginState::Resolved(resolved) => match resolved {
                 ResolvedPlugin::Builtin(builtin) => Plugin::new(builtin)?,
+                ResolvedPlugin::Process(path) => Plugin::new(Box::new(ProcessPlugin::spawn(&path)?))?,
             },
         };
         Ok(started)
@@ -1,4 +1,4 @@
-use super::ProvisionRequest;
+use super::{MergeStrategy, ProvisionRequest};
 use crate::config::Map;
 use crate::plugin_support::PluginStep;
 use pest::Parser;
@@ -100,6 +100,8 @@ pub struct ValueBuilder<T> {
     value: Option<T>,
     from_env: bool,
     required_at: Option<PluginStep>,
+    merge: MergeStrategy,
+    optional: bool,
 }
 
 impl<T> ValueBuilder<T> {
@@ -110,6 +112,8 @@ impl<T> ValueBuilder<T> {
             value: None,
             from_env: false,
             required_at: None,
+            merge: MergeStrategy::default(),
+            optional: false,
         }
     }
 
@@ -134,6 +138,17 @@ impl<T> ValueBuilder<T> {
         self
     }
 
+    pub fn merge(&mut self, strategy: MergeStrategy) -> &mut Self {
+        self.merge = strategy;
+        self
+    }
+
+    /// Leave the value unprovisioned if neither a plugin nor releaserc.toml provides it
+    pub fn optional(&mut self) -> &mut Self {
+        self.optional = true;
+        self
+    }
+
     pub fn build(&mut self) -> Value<T> {
         let key = mem::replace(&mut self.key, String::new());
 
@@ -151,6 +166,8 @@ impl<T> ValueBuilder<T> {
                     required_at: self.required_at.take(),
                     from_env: self.from_env,
                     key,
+                    merge: self.merge,
+                    optional: self.optional,
                 }),
             }
         }
@@ -184,6 +201,7 @@ impl Into<Map<String, Value<serde_json::Value>>> for ValueDefinitionMap {
                     required_at,
                     from_env,
                     key,
+                    merge,
                 } => {
                     let mut kv = Value::builder(&key);
                     if let Some(step) = required_at {
@@ -192,6 +210,7 @@ impl Into<Map<String, Value<serde_json::Value>>> for ValueDefinitionMap {
                     if from_env {
                         kv.load_from_env();
                     }
+                    kv.merge(merge);
                     kv.build()
                 }
             };
@@ -207,6 +226,7 @@ pub enum ValueDefinition {
         required_at: Option<PluginStep>,
         from_env: bool,
         key: String,
+        merge: MergeStrategy,
     },
     Value(serde_json::Value),
 }
@@ -263,6 +283,7 @@ fn parse_value_definition(value: &str) -> Result<ValueDefinition, failure::Error
     let mut required_at = None;
     let mut from_env = false;
     let mut key = String::new();
+    let mut merge = MergeStrategy::default();
 
     for pair in pairs.into_inner() {
         log::trace!("{:#?}", pair);
@@ -277,6 +298,9 @@ fn parse_value_definition(value: &str) -> Result<ValueDefinition, failure::Error
             Rule::key => {
                 key = pair.as_str().into();
             }
+            Rule::merge_strategy => {
+                merge = MergeStrategy::from_str(pair.as_str())?;
+            }
             _ => (),
         }
     }
@@ -285,6 +309,7 @@ fn parse_value_definition(value: &str) -> Result<ValueDefinition, failure::Error
         required_at,
         from_env,
         key,
+        merge,
     })
 }
 
@@ -310,7 +335,9 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: None,
                 from_env: false,
-                key: "key".to_string()
+                key: "key".to_string(),
+                merge: MergeStrategy::Concat,
+                optional: false,
             })
         );
     }
@@ -325,7 +352,9 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: None,
                 from_env: false,
-                key: "key".to_string()
+                key: "key".to_string(),
+                merge: MergeStrategy::Concat,
+                optional: false,
             })
         );
     }
@@ -340,7 +369,24 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: Some(PluginStep::Commit),
                 from_env: false,
-                key: "key".to_string()
+                key: "key".to_string(),
+                merge: MergeStrategy::Concat,
+                optional: false,
+            })
+        );
+    }
+
+    #[test]
+    fn build_optional() {
+        let kv: Value<()> = Value::builder("key").optional().build();
+        assert_eq!(
+            kv.state,
+            ValueState::NeedsProvision(ProvisionRequest {
+                required_at: None,
+                from_env: false,
+                key: "key".to_string(),
+                merge: MergeStrategy::Concat,
+                optional: true,
             })
         );
     }
@@ -371,7 +417,9 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: None,
                 from_env: true,
-                key: "key".to_string()
+                key: "key".to_string(),
+                merge: MergeStrategy::Concat,
+                optional: false,
             })
         );
     }
@@ -441,7 +489,8 @@ mod tests {
             ValueDefinition::From {
                 required_at: None,
                 from_env: false,
-                key: "key".into()
+                key: "key".into(),
+                merge: MergeStrategy::Concat,
             }
         );
     }
@@ -457,7 +506,8 @@ mod tests {
             ValueDefinition::From {
                 required_at: None,
                 from_env: true,
-                key: "key".into()
+                key: "key".into(),
+                merge: MergeStrategy::Concat,
             }
         );
     }
@@ -473,7 +523,8 @@ mod tests {
             ValueDefinition::From {
                 required_at: Some(PluginStep::Commit),
                 from_env: true,
-                key: "key".into()
+                key: "key".into(),
+                merge: MergeStrategy::Concat,
             }
         );
     }
@@ -489,11 +540,35 @@ mod tests {
             ValueDefinition::From {
                 required_at: Some(PluginStep::Commit),
                 from_env: false,
-                key: "key".into()
+                key: "key".into(),
+                merge: MergeStrategy::Concat,
             }
         );
     }
 
+    #[test]
+    fn parse_value_definition_from_merge() {
+        let v: ValueDefinition = parse_value_definition(r#"from:files_to_commit:merge=concat_unique"#)
+            .map_err(pretty_print_error_and_panic)
+            .unwrap();
+
+        assert_eq!(
+            v,
+            ValueDefinition::From {
+                required_at: None,
+                from_env: false,
+                key: "files_to_commit".into(),
+                merge: MergeStrategy::ConcatUnique,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn parse_value_definition_unknown_merge_strategy() {
+        let _v: ValueDefinition = parse_value_definition(r#"from:key:merge=unknown"#).unwrap();
+    }
+
     #[test]
     #[should_panic]
     fn parse_value_definition_unknown_meta_keys() {
@@ -7,7 +7,8 @@ use std::mem;
 
 use super::PluginStep;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
 pub enum Availability {
     Always,
     AfterStep(PluginStep),
@@ -19,10 +20,14 @@ impl Default for Availability {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct ProvisionCapability {
     pub when: Availability,
     pub key: String,
+    /// Keys the value is computed from, provisioned to the providing plugin before the value is queried.
+    /// Such a value is available after the step its keys are, whether the providing plugin runs it or not.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub derived_from: Vec<String>,
 }
 
 impl ProvisionCapability {
@@ -30,6 +35,7 @@ impl ProvisionCapability {
         ProvisionCapabilityBuilder {
             when: Availability::default(),
             key: key.to_owned(),
+            derived_from: Vec::new(),
         }
     }
 }
@@ -37,6 +43,7 @@ impl ProvisionCapability {
 pub struct ProvisionCapabilityBuilder {
     when: Availability,
     key: String,
+    derived_from: Vec<String>,
 }
 
 impl ProvisionCapabilityBuilder {
@@ -45,10 +52,16 @@ impl ProvisionCapabilityBuilder {
         self
     }
 
+    pub fn derived_from(&mut self, key: &str) -> &mut Self {
+        self.derived_from.push(key.to_owned());
+        self
+    }
+
     pub fn build(&mut self) -> ProvisionCapability {
         ProvisionCapability {
             when: mem::replace(&mut self.when, Default::default()),
             key: mem::replace(&mut self.key, String::new()),
+            derived_from: mem::take(&mut self.derived_from),
         }
     }
 }
@@ -58,6 +71,40 @@ pub struct ProvisionRequest {
     pub required_at: Option<PluginStep>,
     pub from_env: bool,
     pub key: String,
+    /// How to combine the values if the key is provided by several plugins
+    #[serde(default)]
+    pub merge: MergeStrategy,
+    /// Whether the value is left unprovisioned if neither a plugin nor releaserc.toml provides it
+    #[serde(default)]
+    pub optional: bool,
+}
+
+/// Strategy of combining the values of a key provided by several plugins
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq, EnumString, IntoStaticStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum MergeStrategy {
+    /// Concatenate the values, flattening arrays
+    #[default]
+    Concat,
+    /// Concatenate the values, flattening arrays and removing duplicates
+    ConcatUnique,
+    /// Take the value that was provided first
+    FirstWins,
+    /// Take the value that was provided last
+    LastWins,
+    /// Fail if the values differ
+    RequireEqual,
+    /// Recursively merge objects, later values override earlier ones
+    DeepMergeObject,
+    /// Take the highest semantic version
+    MaxSemver,
+}
+
+impl MergeStrategy {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
 }
 
 #[derive(Fail, Debug, Clone)]
@@ -82,7 +129,8 @@ mod tests {
             cap,
             ProvisionCapability {
                 when: Availability::Always,
-                key: "key".to_string()
+                key: "key".to_string(),
+                derived_from: vec![],
             }
         )
     }
@@ -96,7 +144,24 @@ mod tests {
             cap,
             ProvisionCapability {
                 when: Availability::AfterStep(PluginStep::PreFlight),
-                key: "key".to_string()
+                key: "key".to_string(),
+                derived_from: vec![],
+            }
+        )
+    }
+
+    #[test]
+    fn provision_capability_build_derived() {
+        let cap = ProvisionCapability::builder("key")
+            .after_step(PluginStep::DeriveNextVersion)
+            .derived_from("next_version")
+            .build();
+        assert_eq!(
+            cap,
+            ProvisionCapability {
+                when: Availability::AfterStep(PluginStep::DeriveNextVersion),
+                key: "key".to_string(),
+                derived_from: vec!["next_version".to_string()],
             }
         )
     }
@@ -2,14 +2,26 @@
 
 pub const PROJECT_ROOT: &str = "project_root";
 pub const DRY_RUN: &str = "dry_run";
+/// Name of the monorepo package being released, `null` outside of monorepos
+pub const PACKAGE: &str = "package";
 
 pub const CURRENT_VERSION: &str = "current_version";
 pub const NEXT_VERSION: &str = "next_version";
+/// Pre-release identifier of the release, `null` for stable releases
+pub const RELEASE_CHANNEL: &str = "release_channel";
+/// Range of the maintenance branch being released, `null` for other branches
+pub const RELEASE_RANGE: &str = "release_range";
 
 pub const GIT_REMOTE: &str = "git_remote";
 pub const GIT_REMOTE_URL: &str = "git_remote_url";
 pub const GIT_BRANCH: &str = "git_branch";
+/// Name of the release tag, available once the next version is known
+pub const RELEASE_TAG: &str = "release_tag";
 
 pub const RELEASE_NOTES: &str = "release_notes";
+/// Release notes as data: sections, commits, breaking changes and authors
+pub const RELEASE_NOTES_DATA: &str = "release_notes_data";
+/// Issues and pull requests referenced by the released commits
+pub const REFERENCED_ISSUES: &str = "referenced_issues";
 
 pub const FILES_TO_COMMIT: &str = "files_to_commit";
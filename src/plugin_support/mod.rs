@@ -8,6 +8,7 @@ pub use self::traits::PluginInterface;
 
 use serde::{Deserialize, Serialize};
 use std::cell::{RefCell, RefMut};
+use std::path::PathBuf;
 use strum::IntoEnumIterator;
 
 pub struct RawPlugin {
@@ -80,10 +81,14 @@ impl RawPluginState {
 pub enum UnresolvedPlugin {
     Builtin,
     Cargo { package: String, version: String },
+    Path { path: String },
+    Binary { command: String },
 }
 
 pub enum ResolvedPlugin {
     Builtin(Box<dyn PluginInterface>),
+    /// Plugin binary speaking JSON-RPC over stdio
+    Process(PathBuf),
 }
 
 #[derive(
@@ -1,5 +1,11 @@
+pub mod openrpc;
 pub mod response;
+pub mod rpc;
 
+use std::convert::TryFrom;
+use std::fmt;
+
+use failure::Fail;
 use serde::{Deserialize, Serialize};
 
 pub type GitRevision = String;
@@ -11,3 +17,92 @@ pub struct Version {
     pub rev: GitRevision,
     pub semver: Option<semver::Version>,
 }
+
+/// Versions released from a maintenance branch, written as `<major>.x` or `<major>.<minor>.x`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionRange {
+    pub major: u64,
+    pub minor: Option<u64>,
+}
+
+impl VersionRange {
+    pub fn contains(&self, version: &semver::Version) -> bool {
+        version.major == self.major && self.minor.into_iter().all(|minor| version.minor == minor)
+    }
+}
+
+impl TryFrom<String> for VersionRange {
+    type Error = InvalidVersionRange;
+
+    fn try_from(range: String) -> Result<Self, Self::Error> {
+        let parts: Vec<_> = range.split('.').collect();
+        let number = |part: &str| part.parse::<u64>().map_err(|_| InvalidVersionRange(range.clone()));
+
+        match parts.as_slice() {
+            [major, "x"] => Ok(VersionRange {
+                major: number(major)?,
+                minor: None,
+            }),
+            [major, minor, "x"] => Ok(VersionRange {
+                major: number(major)?,
+                minor: Some(number(minor)?),
+            }),
+            _ => Err(InvalidVersionRange(range.clone())),
+        }
+    }
+}
+
+impl From<VersionRange> for String {
+    fn from(range: VersionRange) -> Self {
+        range.to_string()
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.minor {
+            Some(minor) => write!(f, "{}.{}.x", self.major, minor),
+            None => write!(f, "{}.x", self.major),
+        }
+    }
+}
+
+#[derive(Fail, Debug)]
+#[fail(
+    display = "invalid version range {:?}, expected `<major>.x` or `<major>.<minor>.x`",
+    _0
+)]
+pub struct InvalidVersionRange(String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(range: &str) -> Result<VersionRange, InvalidVersionRange> {
+        VersionRange::try_from(range.to_owned())
+    }
+
+    fn version(version: &str) -> semver::Version {
+        semver::Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn version_ranges() {
+        let major = range("1.x").unwrap();
+        assert_eq!(major.to_string(), "1.x");
+        assert!(major.contains(&version("1.0.0")));
+        assert!(major.contains(&version("1.9.3")));
+        assert!(!major.contains(&version("2.0.0")));
+
+        let minor = range("1.2.x").unwrap();
+        assert_eq!(minor.to_string(), "1.2.x");
+        assert!(minor.contains(&version("1.2.7")));
+        assert!(minor.contains(&version("1.2.8-rc.1")));
+        assert!(!minor.contains(&version("1.3.0")));
+
+        for invalid in &["1", "1.2.3", "x", "1.x.x", "v1.x", ""] {
+            assert!(range(invalid).is_err(), "{:?} must be rejected", invalid);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! OpenRPC service description of a plugin
+//!
+//! Plugins describe themselves with an [OpenRPC](https://spec.open-rpc.org) document returned from `rpc.discover`:
+//!
+//!  - every implemented step is a method tagged with `step`
+//!  - `get_value` lists the provided keys and their availability in the `x-provisions` extension
+//!  - `set_config` describes the accepted configuration keys as properties of its `config` param schema
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Map;
+use crate::plugin_support::flow::{ProvisionCapability, Value};
+use crate::plugin_support::PluginStep;
+
+pub const OPENRPC_VERSION: &str = "1.2.1";
+
+pub const STEP_TAG: &str = "step";
+pub const GET_VALUE_METHOD: &str = "get_value";
+pub const SET_CONFIG_METHOD: &str = "set_config";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenRpcDocument {
+    pub openrpc: String,
+    pub info: Info,
+    pub methods: Vec<Method>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Info {
+    pub title: String,
+    pub version: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Method {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub params: Vec<ContentDescriptor>,
+    pub result: ContentDescriptor,
+    /// Keys provided through `get_value` and their availability
+    #[serde(rename = "x-provisions", default, skip_serializing_if = "Vec::is_empty")]
+    pub provisions: Vec<ProvisionCapability>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContentDescriptor {
+    pub name: String,
+    #[serde(default)]
+    pub schema: serde_json::Value,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+}
+
+/// Configuration key accepted by a plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigKey {
+    pub name: String,
+    pub protected: bool,
+}
+
+impl OpenRpcDocument {
+    /// Build the service description from the plugin methods, capabilities and its initial configuration
+    pub fn describe(
+        name: &str,
+        steps: &[PluginStep],
+        caps: Vec<ProvisionCapability>,
+        config: &Map<String, Value<serde_json::Value>>,
+    ) -> Self {
+        let mut methods: Vec<Method> = steps
+            .iter()
+            .map(|step| Method {
+                name: step.as_str().to_owned(),
+                tags: vec![Tag {
+                    name: STEP_TAG.to_owned(),
+                }],
+                params: vec![],
+                result: ContentDescriptor::null(),
+                provisions: vec![],
+            })
+            .collect();
+
+        methods.push(Method {
+            name: GET_VALUE_METHOD.to_owned(),
+            tags: vec![],
+            params: vec![ContentDescriptor {
+                name: "key".to_owned(),
+                schema: serde_json::json!({
+                    "type": "string",
+                    "enum": caps.iter().map(|cap| cap.key.as_str()).collect::<Vec<_>>(),
+                }),
+                required: true,
+            }],
+            result: ContentDescriptor {
+                name: "value".to_owned(),
+                schema: serde_json::json!({}),
+                required: false,
+            },
+            provisions: caps,
+        });
+
+        let properties = config
+            .iter()
+            .map(|(key, value)| {
+                let mut schema = serde_json::json!({ "x-protected": value.protected });
+                if value.is_ready() {
+                    schema["default"] = value.as_value().clone();
+                }
+                (key.clone(), schema)
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        methods.push(Method {
+            name: SET_CONFIG_METHOD.to_owned(),
+            tags: vec![],
+            params: vec![ContentDescriptor {
+                name: "config".to_owned(),
+                schema: serde_json::json!({
+                    "type": "object",
+                    "properties": properties,
+                }),
+                required: true,
+            }],
+            result: ContentDescriptor::null(),
+            provisions: vec![],
+        });
+
+        OpenRpcDocument {
+            openrpc: OPENRPC_VERSION.to_owned(),
+            info: Info {
+                title: name.to_owned(),
+                version: env!("CARGO_PKG_VERSION").to_owned(),
+            },
+            methods,
+        }
+    }
+
+    /// Steps implemented by the plugin
+    pub fn steps(&self) -> Vec<PluginStep> {
+        self.methods
+            .iter()
+            .filter(|method| method.tags.iter().any(|tag| tag.name == STEP_TAG))
+            .filter_map(|method| match PluginStep::from_str(&method.name) {
+                Ok(step) => Some(step),
+                Err(_) => {
+                    log::warn!(
+                        "plugin {:?} advertises unknown step {:?}, ignoring it",
+                        self.info.title,
+                        method.name
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Keys the plugin provides through `get_value`
+    pub fn provision_capabilities(&self) -> Vec<ProvisionCapability> {
+        self.method(GET_VALUE_METHOD)
+            .map(|method| method.provisions.clone())
+            .unwrap_or_default()
+    }
+
+    /// Configuration keys accepted by `set_config`
+    pub fn config_keys(&self) -> Vec<ConfigKey> {
+        let properties = self
+            .method(SET_CONFIG_METHOD)
+            .and_then(|method| method.params.iter().find(|param| param.name == "config"))
+            .and_then(|param| param.schema.get("properties"))
+            .and_then(serde_json::Value::as_object);
+
+        match properties {
+            Some(properties) => properties
+                .iter()
+                .map(|(name, schema)| ConfigKey {
+                    name: name.clone(),
+                    protected: schema
+                        .get("x-protected")
+                        .and_then(serde_json::Value::as_bool)
+                        .unwrap_or(false),
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn method(&self, name: &str) -> Option<&Method> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+}
+
+impl ContentDescriptor {
+    fn null() -> Self {
+        ContentDescriptor {
+            name: "null".to_owned(),
+            schema: serde_json::json!({ "type": "null" }),
+            required: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document() -> OpenRpcDocument {
+        let steps = [PluginStep::PreFlight, PluginStep::Commit];
+        let caps = vec![
+            ProvisionCapability::builder("always_key").build(),
+            ProvisionCapability::builder("late_key")
+                .after_step(PluginStep::Commit)
+                .build(),
+        ];
+        let config = vec![
+            ("plain".to_owned(), Value::with_value("plain", serde_json::json!(true))),
+            ("guarded".to_owned(), Value::protected("guarded")),
+        ]
+        .into_iter()
+        .collect();
+
+        OpenRpcDocument::describe("test", &steps, caps, &config)
+    }
+
+    #[test]
+    fn describe_steps() {
+        assert_eq!(document().steps(), vec![PluginStep::PreFlight, PluginStep::Commit]);
+    }
+
+    #[test]
+    fn describe_provision_capabilities() {
+        assert_eq!(
+            document().provision_capabilities(),
+            vec![
+                ProvisionCapability::builder("always_key").build(),
+                ProvisionCapability::builder("late_key")
+                    .after_step(PluginStep::Commit)
+                    .build(),
+            ]
+        );
+    }
+
+    #[test]
+    fn describe_config_keys() {
+        let mut keys = document().config_keys();
+        keys.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(
+            keys,
+            vec![
+                ConfigKey {
+                    name: "guarded".into(),
+                    protected: true
+                },
+                ConfigKey {
+                    name: "plain".into(),
+                    protected: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn roundtrip_through_json() {
+        let doc = document();
+        let json = serde_json::to_value(&doc).unwrap();
+        assert_eq!(json["openrpc"], OPENRPC_VERSION);
+        let parsed: OpenRpcDocument = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, doc);
+    }
+
+    #[test]
+    fn parse_external_document() {
+        let json = serde_json::json!({
+            "openrpc": "1.2.1",
+            "info": { "title": "external", "version": "0.1.0" },
+            "methods": [
+                { "name": "publish", "tags": [{ "name": "step" }], "params": [], "result": { "name": "null" } },
+                { "name": "unrelated", "params": [], "result": { "name": "null" } },
+                {
+                    "name": "get_value",
+                    "params": [{ "name": "key", "schema": { "type": "string" } }],
+                    "result": { "name": "value" },
+                    "x-provisions": [{ "key": "url", "when": { "after_step": "publish" } }]
+                }
+            ]
+        });
+
+        let doc: OpenRpcDocument = serde_json::from_value(json).unwrap();
+        assert_eq!(doc.steps(), vec![PluginStep::Publish]);
+        assert_eq!(
+            doc.provision_capabilities(),
+            vec![ProvisionCapability::builder("url")
+                .after_step(PluginStep::Publish)
+                .build()]
+        );
+        assert!(doc.config_keys().is_empty());
+    }
+}
@@ -1,5 +1,5 @@
-use std::ops::Try;
 
+use super::openrpc::OpenRpcDocument;
 use super::Warning;
 use crate::plugin_support::flow::ProvisionCapability;
 use crate::plugin_support::PluginStep;
@@ -22,32 +22,39 @@ impl<T> PluginResponse<T> {
     }
 }
 
-impl<T> Try for PluginResponse<T> {
-    type Ok = T;
-    type Error = failure::Error;
-
-    fn into_result(self) -> Result<Self::Ok, Self::Error> {
+// COMPAT-BEGIN
+impl<T> PluginResponse<T> {
+    pub fn into_result(self) -> Result<T, failure::Error> {
         self.warnings.iter().for_each(|w| log::warn!("{}", w));
         match self.body {
             PluginResponseBody::Error(err) => Err(err),
             PluginResponseBody::Data(data) => Ok(data),
         }
     }
-
-    fn from_error(v: Self::Error) -> Self {
-        PluginResponse {
-            warnings: vec![],
-            body: PluginResponseBody::Error(v),
-        }
+    pub fn from_error(v: failure::Error) -> Self {
+        PluginResponse { warnings: vec![], body: PluginResponseBody::Error(v) }
     }
-
-    fn from_ok(v: Self::Ok) -> Self {
-        PluginResponse {
-            warnings: vec![],
-            body: PluginResponseBody::Data(v),
+    pub fn from_ok(v: T) -> Self {
+        PluginResponse { warnings: vec![], body: PluginResponseBody::Data(v) }
+    }
+}
+impl<T> std::ops::Try for PluginResponse<T> {
+    type Output = T;
+    type Residual = Result<std::convert::Infallible, failure::Error>;
+    fn from_output(v: T) -> Self { Self::from_ok(v) }
+    fn branch(self) -> std::ops::ControlFlow<Self::Residual, T> {
+        match self.into_result() {
+            Ok(v) => std::ops::ControlFlow::Continue(v),
+            Err(e) => std::ops::ControlFlow::Break(Err(e)),
         }
     }
 }
+impl<T, E: Into<failure::Error>> std::ops::FromResidual<Result<std::convert::Infallible, E>> for PluginResponse<T> {
+    fn from_residual(r: Result<std::convert::Infallible, E>) -> Self {
+        match r { Err(e) => Self::from_error(e.into()), Ok(n) => match n {} }
+    }
+}
+// COMPAT-END
 
 pub struct PluginResponseBuilder<T> {
     warnings: Vec<Warning>,
@@ -115,3 +122,5 @@ pub type Config = PluginResponse<serde_json::Value>;
 
 pub type Methods = PluginResponse<MethodsData>;
 pub type MethodsData = Vec<PluginStep>;
+
+pub type Discover = PluginResponse<OpenRpcDocument>;
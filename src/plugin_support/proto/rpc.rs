@@ -0,0 +1,261 @@
+//! JSON-RPC 2.0 protocol for out-of-process plugins
+//!
+//! Messages are exchanged over the plugin process stdio, one JSON document per line:
+//! requests are written to the plugin stdin and responses are read from its stdout.
+//! Plugin stderr is left attached to the semantic-rs stderr, so plugins may log freely there.
+//!
+//! Every [PluginInterface](crate::plugin_support::PluginInterface) method maps to the RPC method of the same name
+//! (`name`, `methods`, `provision_capabilities`, `get_config`, `set_config`, `get_value`, `pre_flight`, ...).
+//!
+//! Successful calls return `{ "data": <value>, "warnings": [...] }` as a result,
+//! failed calls return a regular JSON-RPC error object, optionally carrying `{ "warnings": [...] }` as error data.
+use std::io::{BufRead, Write};
+
+use failure::Fail;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::response::PluginResponse;
+use super::Warning;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+pub type RequestId = u64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Request {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<serde_json::Value>,
+}
+
+impl Request {
+    pub fn new(id: RequestId, method: &str, params: Option<serde_json::Value>) -> Self {
+        Request {
+            jsonrpc: JSONRPC_VERSION.to_owned(),
+            id,
+            method: method.to_owned(),
+            params,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Option<RequestId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<ResponseResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ResponseError>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseResult {
+    #[serde(default)]
+    pub data: serde_json::Value,
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<ResponseErrorData>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseErrorData {
+    #[serde(default)]
+    pub warnings: Vec<Warning>,
+}
+
+/// Client side of the protocol, generic over the transport
+pub struct RpcClient<R, W> {
+    reader: R,
+    writer: W,
+    next_id: RequestId,
+}
+
+impl<R: BufRead, W: Write> RpcClient<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        RpcClient {
+            reader,
+            writer,
+            next_id: 0,
+        }
+    }
+
+    /// Call the remote method and convert the reply into a `PluginResponse`
+    pub fn call<T: DeserializeOwned>(&mut self, method: &str, params: Option<serde_json::Value>) -> PluginResponse<T> {
+        match self.exchange(method, params) {
+            Ok(response) => response_into_plugin_response(method, response),
+            Err(err) => PluginResponse::builder().error(err),
+        }
+    }
+
+    fn exchange(&mut self, method: &str, params: Option<serde_json::Value>) -> Result<Response, failure::Error> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = Request::new(id, method, params);
+        log::trace!("rpc request: {:?}", request);
+
+        let mut line = serde_json::to_string(&request)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.flush()?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(Error::UnexpectedEof(method.to_owned()).into());
+        }
+
+        let response: Response =
+            serde_json::from_str(&line).map_err(|err| Error::MalformedResponse(method.to_owned(), err.to_string()))?;
+        log::trace!("rpc response: {:?}", response);
+
+        if response.id != Some(id) {
+            return Err(Error::IdMismatch(id, response.id).into());
+        }
+
+        Ok(response)
+    }
+}
+
+fn response_into_plugin_response<T: DeserializeOwned>(method: &str, response: Response) -> PluginResponse<T> {
+    let mut builder = PluginResponse::builder();
+
+    match (response.result, response.error) {
+        (_, Some(error)) => {
+            if let Some(data) = error.data {
+                data.warnings.into_iter().for_each(|w| {
+                    builder.warning(w);
+                });
+            }
+            builder.error(Error::Remote(error.code, error.message))
+        }
+        (Some(result), None) => {
+            result.warnings.into_iter().for_each(|w| {
+                builder.warning(w);
+            });
+            match serde_json::from_value::<T>(result.data) {
+                Ok(data) => builder.body(data),
+                Err(err) => builder.error(Error::MalformedResponse(method.to_owned(), err.to_string())),
+            }
+        }
+        (None, None) => builder.error(Error::MalformedResponse(
+            method.to_owned(),
+            "neither result nor error is present".into(),
+        )),
+    }
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "plugin process closed its stdout while handling {:?}", _0)]
+    UnexpectedEof(String),
+    #[fail(display = "malformed response to {:?}: {}", _0, _1)]
+    MalformedResponse(String, String),
+    #[fail(display = "response id mismatch: expected {}, got {:?}", _0, _1)]
+    IdMismatch(RequestId, Option<RequestId>),
+    #[fail(display = "plugin returned error {}: {}", _0, _1)]
+    Remote(i64, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::ops::Try;
+
+    fn client_with_replies(replies: &[&str]) -> RpcClient<Cursor<Vec<u8>>, Vec<u8>> {
+        let mut input = String::new();
+        for reply in replies {
+            input.push_str(reply);
+            input.push('\n');
+        }
+        RpcClient::new(Cursor::new(input.into_bytes()), Vec::new())
+    }
+
+    fn written_requests(client: &RpcClient<Cursor<Vec<u8>>, Vec<u8>>) -> Vec<Request> {
+        String::from_utf8_lossy(&client.writer)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn serialize_request_without_params() {
+        let request = Request::new(0, "name", None);
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(json, r#"{"jsonrpc":"2.0","id":0,"method":"name"}"#);
+    }
+
+    #[test]
+    fn call_success() {
+        let mut client = client_with_replies(&[r#"{"jsonrpc":"2.0","id":0,"result":{"data":"plugin"}}"#]);
+        let name: String = client.call("name", None).into_result().unwrap();
+        assert_eq!(name, "plugin");
+        assert_eq!(written_requests(&client), vec![Request::new(0, "name", None)]);
+    }
+
+    #[test]
+    fn call_passes_params_and_increments_id() {
+        let mut client = client_with_replies(&[
+            r#"{"jsonrpc":"2.0","id":0,"result":{"data":null}}"#,
+            r#"{"jsonrpc":"2.0","id":1,"result":{"data":42}}"#,
+        ]);
+
+        let params = serde_json::json!({ "config": {} });
+        let () = client.call("set_config", Some(params.clone())).into_result().unwrap();
+        let params_get = serde_json::json!({ "key": "answer" });
+        let answer: u32 = client
+            .call("get_value", Some(params_get.clone()))
+            .into_result()
+            .unwrap();
+        assert_eq!(answer, 42);
+
+        assert_eq!(
+            written_requests(&client),
+            vec![
+                Request::new(0, "set_config", Some(params)),
+                Request::new(1, "get_value", Some(params_get)),
+            ]
+        );
+    }
+
+    #[test]
+    fn call_remote_error() {
+        let mut client = client_with_replies(&[
+            r#"{"jsonrpc":"2.0","id":0,"error":{"code":-32601,"message":"method not implemented"}}"#,
+        ]);
+        let result: Result<(), _> = client.call("notify", None).into_result();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn call_id_mismatch() {
+        let mut client = client_with_replies(&[r#"{"jsonrpc":"2.0","id":5,"result":{"data":null}}"#]);
+        let result: Result<(), _> = client.call("pre_flight", None).into_result();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn call_unexpected_eof() {
+        let mut client = client_with_replies(&[]);
+        let result: Result<(), _> = client.call("pre_flight", None).into_result();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn call_wrong_data_type() {
+        let mut client = client_with_replies(&[r#"{"jsonrpc":"2.0","id":0,"result":{"data":"not a list"}}"#]);
+        let result: Result<Vec<String>, _> = client.call("methods", None).into_result();
+        assert!(result.is_err());
+    }
+}
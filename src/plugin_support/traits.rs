@@ -1,5 +1,6 @@
 use std::ops::Try;
 
+use super::proto::openrpc::OpenRpcDocument;
 use super::proto::response::{self, PluginResponse};
 use crate::plugin_support::flow::{FlowError, Value};
 use std::collections::HashMap;
@@ -39,6 +40,11 @@ pub trait PluginInterface {
             .body(response::MethodsData::default())
     }
 
+    /// OpenRPC service description of the plugin (`rpc.discover`)
+    fn discover(&self) -> response::Discover {
+        describe(self)
+    }
+
     fn pre_flight(&mut self) -> response::Null {
         not_implemented_response()
     }
@@ -74,6 +80,32 @@ pub trait PluginInterface {
     fn notify(&self) -> response::Null {
         not_implemented_response()
     }
+
+    /// Restore the runtime state when resuming a release from a checkpoint
+    ///
+    /// Called by the kernel instead of `pre_flight` for plugins whose pre-flight ran before the checkpoint.
+    /// The release may have changed the project already, so checks of its state should be skipped.
+    fn resume(&mut self) -> response::Null {
+        self.pre_flight()
+    }
+
+    /// Revert the effects of the wet steps
+    ///
+    /// Called by the kernel in reverse order when an action fails, including on the plugin whose step failed,
+    /// which has to revert whatever it did before the failure.
+    /// Plugins with nothing to revert can rely on the default no-op implementation.
+    fn rollback(&mut self) -> response::Null {
+        PluginResponse::from_ok(())
+    }
+}
+
+/// Derive the OpenRPC service description from `methods`, `provision_capabilities` and `get_config`
+pub fn describe<P: PluginInterface + ?Sized>(plugin: &P) -> response::Discover {
+    let name = plugin.name()?;
+    let steps = plugin.methods()?;
+    let caps = plugin.provision_capabilities()?;
+    let config = serde_json::from_value(plugin.get_config()?)?;
+    PluginResponse::from_ok(OpenRpcDocument::describe(&name, &steps, caps, &config))
 }
 
 fn not_implemented_response<T>() -> PluginResponse<T> {
@@ -16,3 +16,41 @@ impl<T, E> ResultExt<T, E> for Result<T, E> {
         self.map_err(SyncFailure::new)
     }
 }
+
+/// Directory of a test under the system temp dir, removed on drop so that it's cleaned up when an assertion fails too
+#[cfg(test)]
+pub struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// Fresh directory, the leftovers of an aborted run are removed first
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("semantic-rs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TempDir {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}